@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevoids::prelude::*;
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// How many boids there are per 100 by 100 square in 2d, or 100 by 100 by 100 cube in 3d,
+/// so every flock size is about as crowded.
+const DENSITY: f32 = 100.0;
+
+struct Setup {
+    space: &'static str,
+    boids: usize,
+    groups_and_borders: bool,
+}
+
+fn build_app(setup: &Setup) -> App {
+    let three_dimensional = setup.space == "3d";
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, BoidsPlugin))
+        .insert_resource(BoidsConfig {
+            space: if three_dimensional {
+                BoidSpace::ThreeDimensional
+            } else {
+                BoidSpace::TwoDimensional
+            },
+            debug: false,
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / 60.0,
+        )));
+
+    let extent = if three_dimensional {
+        (setup.boids as f32 / DENSITY).cbrt() * 100.0
+    } else {
+        (setup.boids as f32 / DENSITY).sqrt() * 100.0
+    };
+
+    let mut seed = 0x2545_f491_u32;
+    let mut random = move || {
+        // xorshift, so runs are repeatable without pulling in a random number generator
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+
+    for index in 0..setup.boids {
+        let position = Vec3::new(
+            random() * extent,
+            random() * extent,
+            if three_dimensional {
+                random() * extent
+            } else {
+                0.0
+            },
+        );
+
+        let mut boid = app.world.spawn((
+            Boid::new(
+                BoidSpeed::new(40.0, 80.0),
+                BoidTurningStrength::new(0.5, 2.0, 0.5, 8.0),
+                BoidViewConfig::new(270, 8.0, 25.0),
+            ),
+            Transform::from_translation(position),
+        ));
+
+        if setup.groups_and_borders {
+            let group = if index % 2 == 0 {
+                BoidCollisionGroup::GROUP_1
+            } else {
+                BoidCollisionGroup::GROUP_2
+            };
+
+            boid.insert((
+                BoidCollisionGroups::new(group, BoidCollisionGroup::GROUP_1),
+                BoidBorder {
+                    left: Some((0.0, 10.0)),
+                    right: Some((extent, 10.0)),
+                    bottom: Some((0.0, 10.0)),
+                    top: Some((extent, 10.0)),
+                    front: three_dimensional.then_some((0.0, 10.0)),
+                    back: three_dimensional.then_some((extent, 10.0)),
+                },
+            ));
+        }
+    }
+
+    // Let the first ticks settle the velocities, so they don't skew the measurements
+    for _ in 0..3 {
+        app.update();
+    }
+
+    app
+}
+
+fn movement(c: &mut Criterion) {
+    let mut group = c.benchmark_group("movement");
+    group.sample_size(20);
+
+    for space in ["2d", "3d"] {
+        for groups_and_borders in [false, true] {
+            for boids in [100, 1_000, 10_000] {
+                let setup = Setup {
+                    space,
+                    boids,
+                    groups_and_borders,
+                };
+                let name = if groups_and_borders {
+                    format!("{space}/groups_and_borders")
+                } else {
+                    format!("{space}/plain")
+                };
+
+                let mut app = build_app(&setup);
+                group.bench_with_input(BenchmarkId::new(name, boids), &boids, |b, _| {
+                    b.iter(|| app.update())
+                });
+            }
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, movement);
+criterion_main!(benches);
@@ -24,6 +24,12 @@ fn main() {
             space: BoidSpace::TwoDimensional,
             debug: false,
         })
+        // Red and cyan boids see each other, but steer away instead of flocking together
+        .insert_resource(BoidGroupRelations::default().with_mutual(
+            BoidCollisionGroup::GROUP_1,
+            BoidCollisionGroup::GROUP_2,
+            BoidGroupRelation::new(-0.5, 0.0, 2.0),
+        ))
         .add_systems(Startup, setup)
         .run()
 }
@@ -79,7 +85,10 @@ fn setup(
                     front: None,
                     back: None,
                 },
-                group,
+                BoidCollisionGroups::new(
+                    group,
+                    BoidCollisionGroup::GROUP_1 | BoidCollisionGroup::GROUP_2,
+                ),
             ));
         }
     }
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use bevoids::prelude::*;
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+const BOID_MIN_SPEED: f32 = 400.0;
+const BOID_MAX_SPEED: f32 = 1000.0;
+
+const BOID_COHESION: f32 = 0.1;
+const BOID_SEPARATION: f32 = 0.4;
+const BOID_ALIGNMENT: f32 = 0.14;
+const BOID_BORDER_TURN_STRENGTH: f32 = 200.0;
+
+const BOID_FOV: u32 = 240;
+const BOID_VIEW_RANGE: f32 = 120.0;
+const BOID_PROTECTED_RANGE: f32 = 60.0;
+
+const BORDER_WIDTH: f32 = 2400.0;
+const BORDER_HEIGHT: f32 = 1300.0;
+const BORDER_MARGIN: f32 = 400.0;
+
+const TICKS: u32 = 600;
+
+/// Simulates boids without a window or renderer, and prints how many ticks per second
+/// the simulation runs at.
+///
+/// Run it in release mode, with the amount of boids as an optional argument:
+/// `cargo run --release --example headless -- 5000`
+fn main() {
+    let boids = std::env::args()
+        .nth(1)
+        .and_then(|boids| boids.parse::<u32>().ok())
+        .unwrap_or(1000);
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, BoidsPlugin))
+        .insert_resource(BoidsConfig {
+            space: BoidSpace::TwoDimensional,
+            debug: false,
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / 60.0,
+        )));
+
+    let columns = (boids as f32).sqrt().ceil() as u32;
+    let spacer = BORDER_WIDTH / columns as f32;
+
+    for index in 0..boids {
+        let x = (index % columns) as f32 * spacer - BORDER_WIDTH / 2.0;
+        let y = (index / columns) as f32 * spacer - BORDER_HEIGHT / 2.0;
+
+        app.world.spawn((
+            Transform::from_xyz(x, y, 0.0),
+            Boid::new(
+                BoidSpeed::new(BOID_MIN_SPEED, BOID_MAX_SPEED),
+                BoidTurningStrength::new(
+                    BOID_COHESION,
+                    BOID_SEPARATION,
+                    BOID_ALIGNMENT,
+                    BOID_BORDER_TURN_STRENGTH,
+                ),
+                BoidViewConfig::new(BOID_FOV, BOID_PROTECTED_RANGE, BOID_VIEW_RANGE),
+            ),
+            BoidBorder {
+                left: Some((-BORDER_WIDTH / 2.0, BORDER_MARGIN)),
+                right: Some((BORDER_WIDTH / 2.0, BORDER_MARGIN)),
+                top: Some((BORDER_HEIGHT / 2.0, BORDER_MARGIN)),
+                bottom: Some((-BORDER_HEIGHT / 2.0, BORDER_MARGIN)),
+                ..default()
+            },
+        ));
+    }
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        app.update();
+    }
+    let elapsed = start.elapsed();
+
+    let stats = app.world.resource::<BoidStats>();
+    println!(
+        "{boids} boids: {:.1} ticks per second, {:.2} ms per tick, {:.1} boids checked per boid",
+        TICKS as f32 / elapsed.as_secs_f32(),
+        elapsed.as_secs_f32() * 1000.0 / TICKS as f32,
+        stats.average_checked(),
+    );
+}
@@ -1,4 +1,30 @@
-use bevy::prelude::{Component, Plugin, Resource, Update, Vec3};
+use std::ops::{Add, Mul, Sub};
+
+use bevy::{
+    gizmos::config::GizmoConfigStore,
+    math::Affine3A,
+    prelude::{
+        not, resource_exists, Assets, Component, Entity, GlobalTransform, IntoSystemConfigs,
+        IntoSystemSetConfigs, Mesh, Parent, Plugin, Quat, Query, Resource, SystemSet, Transform,
+        Update, Vec2, Vec3,
+    },
+};
+
+use crate::{
+    debug::{
+        self, BoidDebugData, BoidDebugFocus, BoidDebugInfo, BoidDebugRecord, BoidForces, BoidRule,
+    },
+    diagnostics::BoidStats,
+    environment::{BoidEnvironment, BoidSusceptibility},
+    flow_field::BoidFlowField,
+    leader::{self, BoidLeader, FollowLeader},
+    obstacles::{self, BoidObstacleBvh, BoidWhiskers},
+    path::BoidPath,
+    recording::BoidPlayback,
+    sdf::BoidSdfBoundary,
+    spatial::{BoidGrid, BoidGridConfig},
+    terrain::{BoidAltitude, BoidTerrain},
+};
 
 /// The plugin you have to add to use boids.
 ///
@@ -7,17 +33,46 @@ pub struct BoidsPlugin;
 
 impl Plugin for BoidsPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(
-            Update,
-            (
-                systems::handle_boid_gizmos,
-                systems::handle_boid_movement,
-                systems::handle_boid_orientation,
-            ),
-        );
+        app.init_resource::<BoidGroupRelations>()
+            .init_resource::<BoidObstacleBvh>()
+            .init_resource::<BoidDebugData>()
+            .init_resource::<BoidStats>()
+            .configure_sets(Update, (BoidSet::Movement, BoidSet::Orientation).chain())
+            .add_systems(
+                Update,
+                (
+                    (
+                        obstacles::systems::rebuild_boid_obstacles
+                            .run_if(resource_exists::<Assets<Mesh>>),
+                        obstacles::systems::update_boid_obstacle_frames,
+                    )
+                        .chain()
+                        .before(BoidSet::Movement),
+                    debug::systems::handle_boid_gizmos.run_if(resource_exists::<GizmoConfigStore>),
+                    (
+                        leader::systems::track_boid_leaders,
+                        systems::handle_boid_movement,
+                    )
+                        .chain()
+                        .run_if(not(resource_exists::<BoidPlayback>))
+                        .in_set(BoidSet::Movement),
+                    systems::handle_boid_orientation.in_set(BoidSet::Orientation),
+                ),
+            );
     }
 }
 
+/// The system sets the boid systems run in.
+///
+/// Use these to order your own systems relative to the boids.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BoidSet {
+    /// Steers the boids, and moves them along their velocity.
+    Movement,
+    /// Rotates the boids to face their velocity.
+    Orientation,
+}
+
 /// The configuration resource of the boids.
 ///
 /// This will be moved to a component later on
@@ -29,8 +84,222 @@ pub struct BoidsConfig {
 
 /// Whether the boids are in 3d or 2d space
 pub enum BoidSpace {
+    /// Boids move on the XY plane, and keep their z position, so it can be used for layering.
+    /// Their velocities are stored and integrated as [Vec2]s.
     TwoDimensional,
     ThreeDimensional,
+    /// 3d boids that move along a plane, like herds on the ground of a top-down 3d game.
+    ///
+    /// Velocities and forces are projected onto the plane, boids are kept on it, and they're
+    /// oriented like 3d boids, with the plane's normal as up.
+    Plane(BoidPlane),
+}
+
+impl BoidSpace {
+    /// Removes the part of `vector` that points out of the plane boids move on.
+    ///
+    /// For 2d boids, that's the z axis, so they stay on their layer.
+    pub(crate) fn project_vector(&self, vector: Vec3) -> Vec3 {
+        match self {
+            BoidSpace::TwoDimensional => vector.truncate().extend(0.0),
+            BoidSpace::ThreeDimensional => vector,
+            BoidSpace::Plane(plane) => plane.project_vector(vector),
+        }
+    }
+}
+
+/// A plane given by a point on it and its normal, used by [BoidSpace::Plane].
+#[derive(Clone, Copy)]
+pub struct BoidPlane {
+    pub origin: Vec3,
+    pub normal: Vec3,
+}
+
+impl BoidPlane {
+    /// The ground plane through the origin, facing +Y.
+    pub const XZ: Self = Self {
+        origin: Vec3::ZERO,
+        normal: Vec3::Y,
+    };
+
+    pub fn new(origin: Vec3, normal: Vec3) -> Self {
+        Self {
+            origin,
+            normal: normal.normalize_or_zero(),
+        }
+    }
+
+    /// Removes the part of `vector` along the normal.
+    pub fn project_vector(&self, vector: Vec3) -> Vec3 {
+        vector - self.normal * vector.dot(self.normal)
+    }
+
+    /// The closest point on the plane to `point`.
+    pub fn project_point(&self, point: Vec3) -> Vec3 {
+        point - self.normal * (point - self.origin).dot(self.normal)
+    }
+}
+
+/// Component that sets which axis of a boid's sprite or model faces the way it travels.
+///
+/// Without it, boids are oriented like [BoidModelForward::Auto].
+#[derive(Component, Clone, Copy, Default)]
+pub enum BoidModelForward {
+    /// +Y in 2d, so a sprite's up faces the way it travels, and -Z in 3d, like Bevy's
+    /// [forward](bevy::prelude::Transform::forward).
+    #[default]
+    Auto,
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+    /// A custom rotation, applied on top of the [Auto](BoidModelForward::Auto) orientation.
+    Custom(Quat),
+}
+
+impl BoidModelForward {
+    /// The rotation from the model's own space, to the space where its forward axis is
+    /// the one [Auto](BoidModelForward::Auto) uses.
+    pub(crate) fn offset(&self, space: &BoidSpace) -> Quat {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        match (self, space) {
+            (BoidModelForward::Auto, _) => Quat::IDENTITY,
+            (BoidModelForward::Custom(rotation), _) => *rotation,
+
+            (BoidModelForward::PosX, BoidSpace::TwoDimensional) => Quat::from_rotation_z(FRAC_PI_2),
+            (BoidModelForward::NegX, BoidSpace::TwoDimensional) => {
+                Quat::from_rotation_z(-FRAC_PI_2)
+            }
+            (BoidModelForward::PosY, BoidSpace::TwoDimensional) => Quat::IDENTITY,
+            (BoidModelForward::NegY, BoidSpace::TwoDimensional) => Quat::from_rotation_z(PI),
+            (BoidModelForward::PosZ, BoidSpace::TwoDimensional) => {
+                Quat::from_rotation_x(-FRAC_PI_2)
+            }
+            (BoidModelForward::NegZ, BoidSpace::TwoDimensional) => Quat::from_rotation_x(FRAC_PI_2),
+
+            (BoidModelForward::PosX, _) => Quat::from_rotation_y(FRAC_PI_2),
+            (BoidModelForward::NegX, _) => Quat::from_rotation_y(-FRAC_PI_2),
+            (BoidModelForward::PosY, _) => Quat::from_rotation_x(-FRAC_PI_2),
+            (BoidModelForward::NegY, _) => Quat::from_rotation_x(FRAC_PI_2),
+            (BoidModelForward::PosZ, _) => Quat::from_rotation_y(PI),
+            (BoidModelForward::NegZ, _) => Quat::IDENTITY,
+        }
+    }
+}
+
+/// Component that makes a 3d boid bank into its turns, like a bird or a plane.
+///
+/// The boid is kept upright relative to `up`, and rolls into the turn by an angle
+/// proportional to how hard it's turning. Without it, 3d boids use their previous
+/// forward direction as up, and never roll.
+#[derive(Component, Clone, Copy)]
+pub struct BoidBanking {
+    /// How many radians the boid rolls per unit of sideways acceleration.
+    pub strength: f32,
+    /// The largest angle the boid rolls, in radians.
+    pub max_angle: f32,
+    /// The direction the boid's up points towards when it's flying straight.
+    pub up: Vec3,
+}
+
+impl BoidBanking {
+    pub fn new(strength: f32, max_angle: f32) -> Self {
+        Self {
+            strength,
+            max_angle,
+            up: Vec3::Y,
+        }
+    }
+
+    pub fn with_up(mut self, up: Vec3) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// The rotation of a boid flying along `direction`, banked by its `acceleration`.
+    pub(crate) fn calc_rotation(&self, direction: Vec3, acceleration: Vec3) -> Quat {
+        let upright = Transform::IDENTITY.looking_to(direction, self.up);
+
+        let lateral_acceleration = acceleration.dot(*upright.right());
+        let angle = (lateral_acceleration * self.strength).clamp(-self.max_angle, self.max_angle);
+
+        // Rolling around the direction of travel lowers the right side for positive angles
+        Quat::from_axis_angle(direction, angle) * upright.rotation
+    }
+}
+
+/// Component that smooths out how a boid's orientation follows its velocity, so it doesn't
+/// jitter when the steering forces fluctuate.
+///
+/// Only the [Transform]'s rotation is smoothed. The velocity used by the simulation is
+/// not affected.
+#[derive(Component, Clone, Copy)]
+pub struct BoidOrientationSmoothing {
+    pub mode: BoidSmoothingMode,
+    angular_velocity: Vec3,
+}
+
+impl BoidOrientationSmoothing {
+    /// Turns towards the velocity at a constant angular speed, in radians per second.
+    pub fn slerp(angular_speed: f32) -> Self {
+        Self {
+            mode: BoidSmoothingMode::Slerp { angular_speed },
+            angular_velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Turns towards the velocity with a critically damped spring, which eases in and out
+    /// without overshooting. Higher frequencies make the boid follow its velocity more tightly.
+    pub fn spring(frequency: f32) -> Self {
+        Self {
+            mode: BoidSmoothingMode::Spring { frequency },
+            angular_velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Turns `rotation` towards `target`, over `delta_seconds`.
+    pub(crate) fn smooth(&mut self, rotation: Quat, target: Quat, delta_seconds: f32) -> Quat {
+        match self.mode {
+            BoidSmoothingMode::Slerp { angular_speed } => {
+                let angle = rotation.angle_between(target);
+                if angle <= f32::EPSILON {
+                    return target;
+                }
+
+                rotation.slerp(target, (angular_speed * delta_seconds / angle).min(1.0))
+            }
+            BoidSmoothingMode::Spring { frequency } => {
+                // The rotation from the target to the current rotation, as a scaled axis
+                let mut offset = rotation * target.inverse();
+                if offset.w < 0.0 {
+                    offset = -offset;
+                }
+                let offset = offset.to_scaled_axis();
+
+                // Exact step of a critically damped spring, which is stable for any delta
+                let omega = std::f32::consts::TAU * frequency;
+                let decay = (-omega * delta_seconds).exp();
+                let temp = (self.angular_velocity + offset * omega) * delta_seconds;
+
+                self.angular_velocity = (self.angular_velocity - temp * omega) * decay;
+                let offset = (offset + temp) * decay;
+
+                (Quat::from_scaled_axis(offset) * target).normalize()
+            }
+        }
+    }
+}
+
+/// The ways [BoidOrientationSmoothing] can smooth a boid's orientation.
+#[derive(Clone, Copy)]
+pub enum BoidSmoothingMode {
+    /// Turn at a constant angular speed, in radians per second.
+    Slerp { angular_speed: f32 },
+    /// Follow with a critically damped spring.
+    Spring { frequency: f32 },
 }
 
 /// Represents the turning strength for the different parts that make up the boid's
@@ -49,6 +318,10 @@ pub struct BoidTurningStrength {
     pub alignment: f32,
     /// How strongly the boid turns away from [its borders](BoidBorder)
     pub border: f32,
+    /// How strongly the boid wanders around on its own. See [BoidWander].
+    ///
+    /// Defaults to `0.0`, which disables wandering.
+    pub wander: f32,
 }
 
 impl BoidTurningStrength {
@@ -58,8 +331,98 @@ impl BoidTurningStrength {
             separation,
             alignment,
             border,
+            wander: 0.0,
         }
     }
+
+    pub fn with_wander(mut self, wander: f32) -> Self {
+        self.wander = wander;
+        self
+    }
+}
+
+/// Represents the wander circle of a boid, which makes it meander instead of flying
+/// in a straight line when it has no neighbours.
+///
+/// Each tick, a point on a circle (or sphere in 3d) in front of the boid is picked, and
+/// the boid steers towards it. The point drifts smoothly around the circle, using noise
+/// that is unique to each boid.
+///
+/// How strongly the boid steers is set by the `wander` field of [BoidTurningStrength].
+#[derive(Clone, Copy)]
+pub struct BoidWander {
+    /// How far in front of the boid the center of the wander circle is.
+    pub distance: f32,
+    /// The radius of the wander circle. Larger values relative to `distance` make sharper turns.
+    pub radius: f32,
+    /// How quickly the wander point moves around the circle, in cycles per second.
+    pub rate: f32,
+}
+
+impl BoidWander {
+    pub fn new(distance: f32, radius: f32, rate: f32) -> Self {
+        Self {
+            distance,
+            radius,
+            rate,
+        }
+    }
+
+    fn calc_force(&self, velocity: Vec3, seed: u32, time: f32, space: &BoidSpace) -> Vec3 {
+        let heading = velocity.try_normalize().unwrap_or(Vec3::X);
+        let t = time * self.rate;
+
+        let offset = match space {
+            BoidSpace::TwoDimensional => {
+                let angle = noise(seed, 0, t) * std::f32::consts::PI;
+                Vec3::new(angle.cos(), angle.sin(), 0.0)
+            }
+            BoidSpace::Plane(plane) => {
+                let angle = noise(seed, 0, t) * std::f32::consts::PI;
+                let (x, y) = plane.normal.any_orthonormal_pair();
+                x * angle.cos() + y * angle.sin()
+            }
+            BoidSpace::ThreeDimensional => {
+                Vec3::new(noise(seed, 0, t), noise(seed, 1, t), noise(seed, 2, t))
+                    .normalize_or_zero()
+            }
+        };
+
+        (heading * self.distance + offset * self.radius).normalize_or_zero()
+    }
+}
+
+impl Default for BoidWander {
+    fn default() -> Self {
+        Self::new(2.0, 1.0, 0.5)
+    }
+}
+
+/// Smooth 1d value noise in the range [-1, 1].
+///
+/// `seed` and `channel` pick the noise sequence, so every boid can get its own.
+pub(crate) fn noise(seed: u32, channel: u32, t: f32) -> f32 {
+    fn hash(seed: u32, channel: u32, i: i32) -> f32 {
+        let mut h = seed.wrapping_mul(0x9e37_79b1)
+            ^ channel.wrapping_mul(0x85eb_ca6b)
+            ^ (i as u32).wrapping_mul(0xc2b2_ae35);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x2c1b_3c6d);
+        h ^= h >> 12;
+        h = h.wrapping_mul(0x297a_2d39);
+        h ^= h >> 15;
+
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    let i = t.floor();
+    let f = t - i;
+    let smoothed = f * f * (3.0 - 2.0 * f);
+
+    let a = hash(seed, channel, i as i32);
+    let b = hash(seed, channel, i as i32 + 1);
+
+    a + (b - a) * smoothed
 }
 
 /// Represents the different options for view configuration of a boid
@@ -75,6 +438,8 @@ pub struct BoidViewConfig {
     /// How far a boid can see.
     /// Shouldn't be smaller than `protected_range`
     pub view_range: f32,
+    /// Which of the visible boids count as neighbours.
+    pub neighbourhood: BoidNeighbourhood,
 }
 
 impl BoidViewConfig {
@@ -83,15 +448,126 @@ impl BoidViewConfig {
             fov,
             protected_range,
             view_range,
+            neighbourhood: BoidNeighbourhood::default(),
+        }
+    }
+
+    pub fn with_neighbourhood(mut self, neighbourhood: BoidNeighbourhood) -> Self {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+}
+
+/// Represents how a boid picks the neighbours it flocks with.
+#[derive(Clone, Copy, Default)]
+pub enum BoidNeighbourhood {
+    /// Every boid within the view range is a neighbour.
+    #[default]
+    Metric,
+    /// Only the `k` nearest boids are neighbours, like real starlings that keep track of
+    /// their ~7 closest flockmates. This also bounds the cost of each boid in dense flocks.
+    ///
+    /// If `capped` is set, boids outside the view range are never neighbours. Otherwise
+    /// the nearest boids are used no matter how far away they are.
+    Topological { k: usize, capped: bool },
+}
+
+/// The kernels used to weigh a boid's neighbours by their distance.
+///
+/// Neighbours closer to the boid can be given more influence than those at the edge of
+/// its view range, which makes for smoother flocks.
+#[derive(Clone, Copy, Default)]
+pub struct BoidWeighting {
+    /// The kernel used when steering towards the center of mass of the neighbours.
+    pub coherence: BoidKernel,
+    /// The kernel used when aligning with the heading of the neighbours.
+    pub alignment: BoidKernel,
+    /// The falloff curve of the separation, within the protected range.
+    pub separation: BoidFalloff,
+}
+
+impl BoidWeighting {
+    pub fn new(coherence: BoidKernel, alignment: BoidKernel, separation: BoidFalloff) -> Self {
+        Self {
+            coherence,
+            alignment,
+            separation,
+        }
+    }
+}
+
+/// A weighting kernel, giving the weight of a neighbour at some distance within the view range.
+#[derive(Clone, Copy, Default)]
+pub enum BoidKernel {
+    /// Every neighbour has the same weight.
+    #[default]
+    Uniform,
+    /// The weight falls off linearly, reaching zero at the edge of the view range.
+    Linear,
+    /// The weight falls off with the square of the distance.
+    InverseSquare,
+    /// The weight follows a bell curve. `sigma` is the standard deviation, as a fraction
+    /// of the view range. It's clamped to at least `0.001`.
+    Gaussian { sigma: f32 },
+}
+
+impl BoidKernel {
+    fn weight(&self, distance: f32, range: f32) -> f32 {
+        let x = distance / range;
+
+        match self {
+            BoidKernel::Uniform => 1.0,
+            BoidKernel::Linear => (1.0 - x).max(0.0),
+            BoidKernel::InverseSquare => 1.0 / (x * x).max(0.01),
+            BoidKernel::Gaussian { sigma } => {
+                // A zero sigma would make overlapping boids weigh 0/0
+                let sigma = sigma.max(0.001);
+                (-(x * x) / (2.0 * sigma * sigma)).exp()
+            }
         }
     }
 }
 
+/// The falloff curve of the separation strength within the protected range.
+///
+/// Each curve is zero at the edge of the protected range, and scaled so that
+/// [BoidFalloff::Linear] reaches the protected range itself when the boids overlap.
+#[derive(Clone, Copy, Default)]
+pub enum BoidFalloff {
+    /// `protected_range - distance`.
+    #[default]
+    Linear,
+    /// Grows with the square of how far into the protected range the neighbour is.
+    /// Soft at the edge, and strong up close.
+    Quadratic,
+    /// Smoothstep curve. Soft both at the edge and up close.
+    Smooth,
+    /// Grows with the inverse square of the distance. Very strong up close.
+    InverseSquare,
+}
+
+impl BoidFalloff {
+    fn strength(&self, distance: f32, range: f32) -> f32 {
+        let x = (1.0 - distance / range).clamp(0.0, 1.0);
+
+        range
+            * match self {
+                BoidFalloff::Linear => x,
+                BoidFalloff::Quadratic => x * x,
+                BoidFalloff::Smooth => x * x * (3.0 - 2.0 * x),
+                BoidFalloff::InverseSquare => {
+                    let distance = (distance / range).max(0.1);
+                    1.0 / (distance * distance) - 1.0
+                }
+            }
+    }
+}
+
 /// Represents the min/max speed limits of a boid
 #[derive(Clone, Copy)]
 pub struct BoidSpeed {
-    min: f32,
-    max: f32,
+    pub(crate) min: f32,
+    pub(crate) max: f32,
 }
 
 impl BoidSpeed {
@@ -104,6 +580,11 @@ impl BoidSpeed {
 ///
 /// **NOTE**: This will take control of the entity's [Transform](bevy::prelude::Transform)
 ///
+/// Boids with a [Parent](bevy::prelude::Parent) are simulated in their parent's local space,
+/// so a school of fish in a moving ship swims along with it. They only flock with boids that
+/// share their parent, and their ranges, speeds, borders and paths are in that space too,
+/// while obstacles, leaders and other world space surroundings are converted.
+///
 /// ## Example
 /// ```rust
 /// commands.spawn((
@@ -141,7 +622,29 @@ pub struct Boid {
     ///
     /// **NOTE**: FOV isn't currently implemented.
     pub view_config: BoidViewConfig,
-    velocity: Vec3,
+    /// How the boid weighs its neighbours by their distance.
+    pub weighting: BoidWeighting,
+    /// The boid's wander circle. Only used when the `wander` turning strength isn't zero.
+    pub wander: BoidWander,
+    /// The maximum change in velocity per second.
+    /// When the steering forces exceed it, separation and borders are prioritized over
+    /// alignment, coherence and wandering.
+    ///
+    /// `None` means the acceleration is unlimited.
+    pub max_acceleration: Option<f32>,
+    /// The maximum turn rate of the boid, in radians per second.
+    ///
+    /// `None` means the boid can turn instantly.
+    pub max_turn_rate: Option<f32>,
+    velocity: BoidVelocity,
+    acceleration: Vec3,
+}
+
+/// A boid's velocity. 2d boids store it as a [Vec2], so it can never gain a z component.
+#[derive(Clone, Copy)]
+enum BoidVelocity {
+    Flat(Vec2),
+    Spatial(Vec3),
 }
 
 impl Boid {
@@ -154,18 +657,62 @@ impl Boid {
             speed,
             turning_strength,
             view_config,
-            velocity: Vec3::ZERO,
+            weighting: BoidWeighting::default(),
+            wander: BoidWander::default(),
+            max_acceleration: None,
+            max_turn_rate: None,
+            velocity: BoidVelocity::Spatial(Vec3::ZERO),
+            acceleration: Vec3::ZERO,
         }
     }
 
+    pub fn with_max_acceleration(mut self, max_acceleration: f32) -> Self {
+        self.max_acceleration = Some(max_acceleration);
+        self
+    }
+
+    pub fn with_max_turn_rate(mut self, max_turn_rate: f32) -> Self {
+        self.max_turn_rate = Some(max_turn_rate);
+        self
+    }
+
+    pub fn with_weighting(mut self, weighting: BoidWeighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    pub fn with_wander(mut self, wander: BoidWander) -> Self {
+        self.wander = wander;
+        self
+    }
+
     pub fn velocity(&self) -> Vec3 {
-        self.velocity
+        match self.velocity {
+            BoidVelocity::Flat(velocity) => velocity.extend(0.0),
+            BoidVelocity::Spatial(velocity) => velocity,
+        }
+    }
+
+    /// Overwrites the velocity, dropping the z component if the boid moves in 2d.
+    pub(crate) fn set_velocity(&mut self, velocity: Vec3) {
+        self.velocity = match self.velocity {
+            BoidVelocity::Flat(_) => BoidVelocity::Flat(velocity.truncate()),
+            BoidVelocity::Spatial(_) => BoidVelocity::Spatial(velocity),
+        };
+    }
+
+    /// The boid's change in velocity per second, during the last tick.
+    pub fn acceleration(&self) -> Vec3 {
+        self.acceleration
     }
 }
 
 /// Component used for grouping boids. This effectively means that boids in
 /// different groups ignore each other.
 ///
+/// For finer control over how groups interact, see [BoidCollisionGroups] and
+/// [BoidGroupRelations].
+///
 /// ## How to use
 /// The collision group is used as a bitflag. This means one boid can have multiple
 /// collision groups.
@@ -273,6 +820,179 @@ impl Default for BoidCollisionGroup {
     }
 }
 
+/// Component used for grouping boids, with separate masks for which groups the boid is in,
+/// and which groups it interacts with. This allows asymmetric relations, like scouts that
+/// see everyone, but that nobody else sees.
+///
+/// A boid sees another boid, if its `filters` intersect the other boid's `memberships`.
+/// When this component is present, it takes priority over [BoidCollisionGroup].
+///
+/// ```rust,ignore
+/// // Scouts see every boid
+/// let scout = commands.spawn((
+///    Boid::default(),
+///    BoidCollisionGroups::new(BoidCollisionGroup::GROUP_2, BoidCollisionGroup::ALL)
+/// )).id();
+///
+/// // But the flock only sees itself
+/// let flock = commands.spawn((
+///    Boid::default(),
+///    BoidCollisionGroup::GROUP_1
+/// )).id();
+/// ```
+#[derive(Component, Clone, Copy)]
+pub struct BoidCollisionGroups {
+    /// The groups the boid is in.
+    pub memberships: BoidCollisionGroup,
+    /// The groups the boid interacts with.
+    pub filters: BoidCollisionGroup,
+}
+
+impl BoidCollisionGroups {
+    pub fn new(memberships: BoidCollisionGroup, filters: BoidCollisionGroup) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+
+    /// Whether a boid with these groups sees a boid with the `other` groups.
+    pub fn sees(&self, other: &BoidCollisionGroups) -> bool {
+        self.filters.intersects(other.memberships)
+    }
+
+    /// Picks the groups of a boid, from whichever of the group components it has.
+    pub(crate) fn resolve(
+        groups: Option<&BoidCollisionGroups>,
+        group: Option<&BoidCollisionGroup>,
+    ) -> Option<Self> {
+        groups.copied().or_else(|| group.copied().map(Self::from))
+    }
+}
+
+impl Default for BoidCollisionGroups {
+    fn default() -> Self {
+        Self::new(BoidCollisionGroup::ALL, BoidCollisionGroup::ALL)
+    }
+}
+
+impl From<BoidCollisionGroup> for BoidCollisionGroups {
+    fn from(group: BoidCollisionGroup) -> Self {
+        Self::new(group, group)
+    }
+}
+
+/// Multipliers for how strongly a boid reacts to boids of another [collision group](BoidCollisionGroup).
+///
+/// Negative values invert the rule, so a negative `coherence` makes the boid steer away
+/// from the other group instead of towards it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoidGroupRelation {
+    pub coherence: f32,
+    pub alignment: f32,
+    pub separation: f32,
+}
+
+impl BoidGroupRelation {
+    /// The boids interact like boids in the same group.
+    pub const FLOCK: Self = Self::new(1.0, 1.0, 1.0);
+    /// The boids ignore each other.
+    pub const IGNORE: Self = Self::new(0.0, 0.0, 0.0);
+
+    pub const fn new(coherence: f32, alignment: f32, separation: f32) -> Self {
+        Self {
+            coherence,
+            alignment,
+            separation,
+        }
+    }
+}
+
+impl Default for BoidGroupRelation {
+    fn default() -> Self {
+        Self::FLOCK
+    }
+}
+
+/// Resource defining how boids of different [collision groups](BoidCollisionGroup) interact.
+///
+/// Relations are looked up using the groups the boids are members of, and only apply to
+/// boids whose [filters](BoidCollisionGroups::filters) let them see each other. Pairs of
+/// groups without a relation fully interact.
+///
+/// ## Example
+/// ```rust,ignore
+/// // Red and cyan boids avoid each other, instead of passing through
+/// app.insert_resource(BoidGroupRelations::default().with_mutual(
+///     BoidCollisionGroup::GROUP_1,
+///     BoidCollisionGroup::GROUP_2,
+///     BoidGroupRelation::new(-0.5, 0.0, 2.0),
+/// ));
+/// ```
+#[derive(Resource, Default)]
+pub struct BoidGroupRelations {
+    relations: Vec<(BoidCollisionGroup, BoidCollisionGroup, BoidGroupRelation)>,
+}
+
+impl BoidGroupRelations {
+    /// Sets how boids in `group` react to boids in `other`.
+    /// This doesn't affect how boids in `other` react to boids in `group`.
+    ///
+    /// When boids are in multiple groups, the relation set last takes priority.
+    pub fn set(
+        &mut self,
+        group: BoidCollisionGroup,
+        other: BoidCollisionGroup,
+        relation: BoidGroupRelation,
+    ) -> &mut Self {
+        self.relations.push((group, other, relation));
+        self
+    }
+
+    /// Sets how boids in `group` and `other` react to each other, in both directions.
+    pub fn set_mutual(
+        &mut self,
+        group: BoidCollisionGroup,
+        other: BoidCollisionGroup,
+        relation: BoidGroupRelation,
+    ) -> &mut Self {
+        self.set(group, other, relation).set(other, group, relation)
+    }
+
+    pub fn with(
+        mut self,
+        group: BoidCollisionGroup,
+        other: BoidCollisionGroup,
+        relation: BoidGroupRelation,
+    ) -> Self {
+        self.set(group, other, relation);
+        self
+    }
+
+    pub fn with_mutual(
+        mut self,
+        group: BoidCollisionGroup,
+        other: BoidCollisionGroup,
+        relation: BoidGroupRelation,
+    ) -> Self {
+        self.set_mutual(group, other, relation);
+        self
+    }
+
+    /// Returns how boids in `group` react to boids in `other`, if a relation is set.
+    pub fn get(
+        &self,
+        group: BoidCollisionGroup,
+        other: BoidCollisionGroup,
+    ) -> Option<BoidGroupRelation> {
+        self.relations
+            .iter()
+            .rev()
+            .find(|(a, b, _)| group.intersects(*a) && other.intersects(*b))
+            .map(|(_, _, relation)| *relation)
+    }
+}
+
 /// Represents the border that a boid should stay within
 ///
 /// Each field is optional, to allow for any border you want!
@@ -281,7 +1001,7 @@ impl Default for BoidCollisionGroup {
 /// That means the first value is the actual point in space that the border is.
 /// The margin is added onto that space, and basically chooses when the boid should start turning.
 /// With a smaller margin, you should increase the strength of the [BoidTurningStrength] `border` field
-#[derive(Component, Default)]
+#[derive(Component, Clone, Copy, Default, PartialEq)]
 pub struct BoidBorder {
     pub top: Option<(f32, f32)>,
     pub bottom: Option<(f32, f32)>,
@@ -347,178 +1067,872 @@ impl BoidBorder {
     }
 }
 
+/// The vector math of the flocking rules, so 2d flocks can run them on [Vec2].
+trait BoidVector:
+    Copy + Default + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self>
+{
+    /// Whether the vector has no z axis.
+    const FLAT: bool;
+
+    fn from_vec3(vector: &Vec3) -> Self;
+    fn to_vec3(self) -> Vec3;
+    fn length(self) -> f32;
+    fn length_squared(self) -> f32;
+    fn normalize_or_zero(self) -> Self;
+    fn clamp_length(self, min: f32, max: f32) -> Self;
+    fn clamp_length_max(self, max: f32) -> Self;
+
+    /// Reads the boid's velocity.
+    fn velocity_of(boid: &Boid) -> Self;
+    fn into_velocity(self) -> BoidVelocity;
+
+    /// Rotates `self` towards `target` by at most `max_angle` radians, keeping the
+    /// length of `target`.
+    fn limit_turn(self, target: Self, max_angle: f32, space: &BoidSpace) -> Self;
+}
+
+impl BoidVector for Vec2 {
+    const FLAT: bool = true;
+
+    fn from_vec3(vector: &Vec3) -> Self {
+        vector.truncate()
+    }
+
+    fn to_vec3(self) -> Vec3 {
+        self.extend(0.0)
+    }
+
+    fn length(self) -> f32 {
+        Vec2::length(self)
+    }
+
+    fn length_squared(self) -> f32 {
+        Vec2::length_squared(self)
+    }
+
+    fn normalize_or_zero(self) -> Self {
+        Vec2::normalize_or_zero(self)
+    }
+
+    fn clamp_length(self, min: f32, max: f32) -> Self {
+        Vec2::clamp_length(self, min, max)
+    }
+
+    fn clamp_length_max(self, max: f32) -> Self {
+        Vec2::clamp_length_max(self, max)
+    }
+
+    fn velocity_of(boid: &Boid) -> Self {
+        match boid.velocity {
+            BoidVelocity::Flat(velocity) => velocity,
+            BoidVelocity::Spatial(velocity) => velocity.truncate(),
+        }
+    }
+
+    fn into_velocity(self) -> BoidVelocity {
+        BoidVelocity::Flat(self)
+    }
+
+    fn limit_turn(self, target: Self, max_angle: f32, _space: &BoidSpace) -> Self {
+        let (Some(from), Some(to)) = (self.try_normalize(), target.try_normalize()) else {
+            return target;
+        };
+
+        // Signed, counter-clockwise from `from`
+        let angle = from.angle_between(to);
+        if angle.abs() <= max_angle {
+            return target;
+        }
+
+        Vec2::from_angle(max_angle.copysign(angle)).rotate(from) * target.length()
+    }
+}
+
+impl BoidVector for Vec3 {
+    const FLAT: bool = false;
+
+    fn from_vec3(vector: &Vec3) -> Self {
+        *vector
+    }
+
+    fn to_vec3(self) -> Vec3 {
+        self
+    }
+
+    fn length(self) -> f32 {
+        Vec3::length(self)
+    }
+
+    fn length_squared(self) -> f32 {
+        Vec3::length_squared(self)
+    }
+
+    fn normalize_or_zero(self) -> Self {
+        Vec3::normalize_or_zero(self)
+    }
+
+    fn clamp_length(self, min: f32, max: f32) -> Self {
+        Vec3::clamp_length(self, min, max)
+    }
+
+    fn clamp_length_max(self, max: f32) -> Self {
+        Vec3::clamp_length_max(self, max)
+    }
+
+    fn velocity_of(boid: &Boid) -> Self {
+        boid.velocity()
+    }
+
+    fn into_velocity(self) -> BoidVelocity {
+        BoidVelocity::Spatial(self)
+    }
+
+    fn limit_turn(self, target: Self, max_angle: f32, space: &BoidSpace) -> Self {
+        let (Some(from), Some(to)) = (self.try_normalize(), target.try_normalize()) else {
+            return target;
+        };
+
+        let angle = from.angle_between(to);
+        if angle <= max_angle {
+            return target;
+        }
+
+        let axis = match space {
+            BoidSpace::Plane(plane) => plane.normal * from.cross(to).dot(plane.normal).signum(),
+            _ => from
+                .cross(to)
+                .try_normalize()
+                .unwrap_or_else(|| from.any_orthonormal_vector()),
+        };
+
+        Quat::from_axis_angle(axis, max_angle) * from * target.length()
+    }
+}
+
+/// A boid, as seen by the other boids.
+struct BoidNeighbour<V> {
+    position: V,
+    velocity: V,
+    collision_groups: Option<BoidCollisionGroups>,
+    entity: Entity,
+}
+
+/// The separation, alignment and coherence forces of a boid, before the turning strengths.
+struct BoidFlocking {
+    separation: Vec3,
+    alignment: Vec3,
+    coherence: Vec3,
+}
+
+/// The positions and velocities of every boid at the start of the tick.
+struct BoidSnapshot<V> {
+    boids: Vec<BoidNeighbour<V>>,
+    /// The grid the boids are sorted into, unless every boid has to check every other boid.
+    grid: Option<BoidGrid>,
+    /// The boids that could be neighbours of the boid being steered.
+    candidates: Vec<usize>,
+    /// The neighbours of the boid being steered, as (index, offset, distance, relation).
+    neighbours: Vec<(usize, V, f32, BoidGroupRelation)>,
+    /// The largest view range of all boids.
+    max_view_range: f32,
+    /// How many candidates were checked for being neighbours, over all boids.
+    checked: usize,
+}
+
+impl<V> Default for BoidSnapshot<V> {
+    fn default() -> Self {
+        Self {
+            boids: Vec::new(),
+            grid: None,
+            candidates: Vec::new(),
+            neighbours: Vec::new(),
+            max_view_range: 0.0,
+            checked: 0,
+        }
+    }
+}
+
+impl<V: BoidVector> BoidSnapshot<V> {
+    /// The positions of the neighbours found by the last [calc_flocking](Self::calc_flocking).
+    fn neighbour_positions(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.neighbours
+            .iter()
+            .map(|(index, ..)| self.boids[*index].position.to_vec3())
+    }
+
+    fn push(
+        &mut self,
+        position: &Vec3,
+        velocity: &Vec3,
+        boid: &Boid,
+        collision_groups: Option<BoidCollisionGroups>,
+        entity: Entity,
+    ) {
+        self.boids.push(BoidNeighbour {
+            position: V::from_vec3(position),
+            velocity: V::from_vec3(velocity),
+            collision_groups,
+            entity,
+        });
+        self.max_view_range = self.max_view_range.max(boid.view_config.view_range);
+    }
+
+    /// Sorts the boids into a grid, once they're all pushed.
+    fn build_grid(&mut self, config: Option<&BoidGridConfig>) {
+        let cell_size = config
+            .and_then(|config| config.cell_size)
+            .unwrap_or(self.max_view_range);
+
+        self.grid = BoidGrid::new(
+            cell_size,
+            self.boids.iter().map(|boid| boid.position.to_vec3()),
+        );
+    }
+
+    /// Gathers the neighbours of `boid`, and calculates its flocking forces from them.
+    fn calc_flocking(
+        &mut self,
+        position: &Vec3,
+        boid: &Boid,
+        collision_groups: Option<BoidCollisionGroups>,
+        entity: Entity,
+        relations: &BoidGroupRelations,
+    ) -> BoidFlocking {
+        let position = V::from_vec3(position);
+        let velocity = V::velocity_of(boid);
+
+        self.neighbours.clear();
+        self.candidates.clear();
+
+        let uncapped = matches!(
+            boid.view_config.neighbourhood,
+            BoidNeighbourhood::Topological { capped: false, .. }
+        );
+
+        match &self.grid {
+            Some(grid) if !uncapped => grid.query(
+                position.to_vec3(),
+                boid.view_config.view_range,
+                V::FLAT,
+                &mut self.candidates,
+            ),
+            _ => self.candidates.extend(0..self.boids.len()),
+        }
+        self.checked += self.candidates.len();
+
+        for &index in &self.candidates {
+            let neighbour = &self.boids[index];
+            if neighbour.entity == entity {
+                continue;
+            }
+
+            // Relations only scale how the boid reacts to the neighbours it can see
+            if let (Some(collision_groups), Some(neighbour_collision_groups)) =
+                (collision_groups, neighbour.collision_groups)
+            {
+                if !collision_groups.sees(&neighbour_collision_groups) {
+                    continue;
+                }
+            }
+
+            let relation = relations
+                .get(
+                    collision_groups.unwrap_or_default().memberships,
+                    neighbour.collision_groups.unwrap_or_default().memberships,
+                )
+                .unwrap_or(BoidGroupRelation::FLOCK);
+            if relation == BoidGroupRelation::IGNORE {
+                continue;
+            }
+
+            let offset = neighbour.position - position;
+            let distance = offset.length();
+
+            let in_view = distance <= boid.view_config.view_range;
+
+            if in_view || uncapped {
+                self.neighbours.push((index, offset, distance, relation));
+            }
+        }
+
+        if let BoidNeighbourhood::Topological { k, .. } = boid.view_config.neighbourhood {
+            if self.neighbours.len() > k {
+                if k > 0 {
+                    self.neighbours
+                        .select_nth_unstable_by(k - 1, |(_, _, a, _), (_, _, b, _)| a.total_cmp(b));
+                }
+                self.neighbours.truncate(k);
+            }
+        }
+
+        let mut separation_vector = V::default();
+        let mut alignment_vector = V::default();
+        let mut alignment_weight = 0.0;
+        let mut coherence_vector = V::default();
+        let mut coherence_weight = 0.0;
+
+        for &(index, offset, distance, relation) in &self.neighbours {
+            let neighbour = &self.boids[index];
+
+            if distance < boid.view_config.protected_range {
+                let strength = boid
+                    .weighting
+                    .separation
+                    .strength(distance, boid.view_config.protected_range);
+
+                separation_vector = separation_vector
+                    - offset.normalize_or_zero() * (strength * relation.separation);
+            }
+
+            // Neighbours only count towards the average as much as their relation makes
+            // them matter, so ignored groups don't dilute the boid's own flock
+            let weight = boid
+                .weighting
+                .alignment
+                .weight(distance, boid.view_config.view_range);
+            alignment_vector =
+                alignment_vector + (neighbour.velocity - velocity) * (weight * relation.alignment);
+            alignment_weight += weight * relation.alignment.abs();
+
+            let weight = boid
+                .weighting
+                .coherence
+                .weight(distance, boid.view_config.view_range);
+            coherence_vector = coherence_vector + offset * (weight * relation.coherence);
+            coherence_weight += weight * relation.coherence.abs();
+        }
+
+        BoidFlocking {
+            separation: separation_vector.to_vec3(),
+            alignment: if alignment_weight > 0.0 {
+                alignment_vector.to_vec3() / alignment_weight
+            } else {
+                Vec3::ZERO
+            },
+            coherence: if coherence_weight > 0.0 {
+                coherence_vector.to_vec3() / coherence_weight
+            } else {
+                Vec3::ZERO
+            },
+        }
+    }
+}
+
+/// The space a boid is simulated in.
+///
+/// Boids with a [Parent](bevy::prelude::Parent) move in their parent's local space, so
+/// they're carried along when it moves or rotates. They flock with the boids that share
+/// their parent, while the world space surroundings, like obstacles, leaders and the
+/// terrain, are converted between the spaces.
+#[derive(Clone, Copy)]
+pub(crate) struct BoidFrame {
+    to_world: Affine3A,
+    to_local: Affine3A,
+}
+
+impl BoidFrame {
+    pub(crate) const WORLD: Self = Self {
+        to_world: Affine3A::IDENTITY,
+        to_local: Affine3A::IDENTITY,
+    };
+
+    pub(crate) fn new(parent: &GlobalTransform) -> Self {
+        let to_world = parent.affine();
+
+        Self {
+            to_world,
+            to_local: to_world.inverse(),
+        }
+    }
+
+    /// The space of a boid with `parent`.
+    pub(crate) fn of(parent: Option<&Parent>, parent_query: &Query<&GlobalTransform>) -> Self {
+        parent
+            .and_then(|parent| parent_query.get(parent.get()).ok())
+            .map_or(Self::WORLD, Self::new)
+    }
+
+    pub(crate) fn point_to_world(&self, point: Vec3) -> Vec3 {
+        self.to_world.transform_point3(point)
+    }
+
+    pub(crate) fn point_to_local(&self, point: Vec3) -> Vec3 {
+        self.to_local.transform_point3(point)
+    }
+
+    pub(crate) fn vector_to_world(&self, vector: Vec3) -> Vec3 {
+        self.to_world.transform_vector3(vector)
+    }
+
+    pub(crate) fn vector_to_local(&self, vector: Vec3) -> Vec3 {
+        self.to_local.transform_vector3(vector)
+    }
+
+    /// Converts a length from the local space to world space, assuming a uniform scale.
+    pub(crate) fn length_to_world(&self, length: f32) -> f32 {
+        length * self.to_world.matrix3.determinant().abs().cbrt()
+    }
+
+    /// A copy of `boid`, with its velocity and speed limits in world space.
+    fn boid_to_world(&self, boid: &Boid) -> Boid {
+        Boid {
+            velocity: BoidVelocity::Spatial(self.vector_to_world(boid.velocity())),
+            speed: BoidSpeed::new(
+                self.length_to_world(boid.speed.min),
+                self.length_to_world(boid.speed.max),
+            ),
+            ..*boid
+        }
+    }
+}
+
+/// Accumulates steering forces in order of priority, using Reynolds' prioritized
+/// acceleration allocation.
+///
+/// Each force uses up as much of the budget as its magnitude, even if it cancels out an
+/// earlier one. Once the budget is used up, the last force is truncated, and the rest
+/// are ignored.
+struct BoidSteering<'a, V> {
+    force: V,
+    budget: Option<f32>,
+    /// The sum of the magnitudes of the forces added so far.
+    used: f32,
+    space: &'a BoidSpace,
+    /// What each rule asked for, before the budget.
+    forces: BoidForces,
+}
+
+impl<'a, V: BoidVector> BoidSteering<'a, V> {
+    fn new(budget: Option<f32>, space: &'a BoidSpace) -> Self {
+        Self {
+            force: V::default(),
+            budget,
+            used: 0.0,
+            space,
+            forces: BoidForces::default(),
+        }
+    }
+
+    fn add(&mut self, rule: BoidRule, force: Vec3) {
+        // Only the part of the force along the plane uses up the budget
+        let force = self.space.project_vector(force);
+        *self.forces.get_mut(rule) += force;
+        let force = V::from_vec3(&force);
+
+        let Some(budget) = self.budget else {
+            self.force = self.force + force;
+            return;
+        };
+
+        let remaining = budget - self.used;
+        if remaining <= 0.0 {
+            return;
+        }
+
+        let force = force.clamp_length_max(remaining);
+        self.used += force.length();
+        self.force = self.force + force;
+    }
+}
+
 mod systems {
     use bevy::{
-        prelude::{Color, Entity, Gizmos, Query, Res, Transform, Vec2},
+        ecs::system::SystemParam,
+        prelude::{Entity, Has, Query, Res, ResMut, Transform, Vec2, With},
         time::Time,
+        utils::{HashMap, Instant},
     };
 
     use super::*;
 
+    /// The optional resources and other entities that affect the boids' movement.
+    #[derive(SystemParam)]
+    pub(super) struct BoidSurroundings<'w, 's> {
+        flow_field: Option<Res<'w, BoidFlowField>>,
+        environment: Option<Res<'w, BoidEnvironment>>,
+        terrain: Option<Res<'w, BoidTerrain>>,
+        obstacles: Res<'w, BoidObstacleBvh>,
+        leaders: Query<'w, 's, &'static BoidLeader>,
+        parents: Query<'w, 's, &'static GlobalTransform>,
+    }
+
+    /// Where the movement system reports what the boids did, for debugging and profiling.
+    #[derive(SystemParam)]
+    pub(super) struct BoidReports<'w, 's> {
+        debug_data: ResMut<'w, BoidDebugData>,
+        focus_query: Query<'w, 's, (), With<BoidDebugFocus>>,
+        stats: ResMut<'w, BoidStats>,
+    }
+
+    type BoidMovementData = (
+        &'static mut Transform,
+        &'static mut Boid,
+        Option<&'static BoidBorder>,
+        Option<&'static BoidCollisionGroup>,
+        Option<&'static BoidCollisionGroups>,
+        Option<&'static FollowLeader>,
+        Option<&'static mut BoidPath>,
+        Option<&'static BoidSusceptibility>,
+        Option<&'static BoidAltitude>,
+        Option<&'static BoidSdfBoundary>,
+        Option<&'static BoidWhiskers>,
+        Option<&'static Parent>,
+        (Option<&'static mut BoidDebugInfo>, Has<BoidDebugFocus>),
+        Entity,
+    );
+
     pub(super) fn handle_boid_movement(
         time: Res<Time>,
-        mut boid_query: Query<(
-            &mut Transform,
-            &mut Boid,
-            Option<&BoidBorder>,
-            Option<&BoidCollisionGroup>,
-            Entity,
-        )>,
+        config: Res<BoidsConfig>,
+        relations: Res<BoidGroupRelations>,
+        grid_config: Option<Res<BoidGridConfig>>,
+        mut boid_query: Query<BoidMovementData>,
+        surroundings: BoidSurroundings,
+        mut reports: BoidReports,
     ) {
-        let boids = boid_query
-            .iter()
-            .map(|(transform, boid, _, collision_group, entity)| {
-                (*transform, *boid, collision_group.copied(), entity)
-            })
-            .collect::<Vec<_>>();
-
-        for (mut transform, mut boid, border, collision_group, entity) in boid_query.iter_mut() {
-            let mut movement_vector = Vec3::ZERO;
-
-            let mut separation_vector = Vec3::ZERO;
-            let mut average_velocity = Vec3::ZERO;
-            let mut average_position = Vec3::ZERO;
-            let mut neighbouring_boids = 0;
-
-            for (
-                neighbour_transform,
-                neighbour_boid,
-                neighbour_collision_group,
-                neighbour_entity,
-            ) in &boids
-            {
-                if *neighbour_entity == entity {
-                    continue;
-                }
+        // 2d flocks are simulated on `Vec2`, so the z layer never leaks into them
+        match config.space {
+            BoidSpace::TwoDimensional => move_boids::<Vec2>(
+                &time,
+                &config,
+                &relations,
+                grid_config.as_deref(),
+                &mut boid_query,
+                &surroundings,
+                &mut reports,
+            ),
+            _ => move_boids::<Vec3>(
+                &time,
+                &config,
+                &relations,
+                grid_config.as_deref(),
+                &mut boid_query,
+                &surroundings,
+                &mut reports,
+            ),
+        }
+    }
 
-                if let Some(collision_group) = collision_group {
-                    if let Some(neighbour_collision_group) = neighbour_collision_group {
-                        if !collision_group.intersects(*neighbour_collision_group) {
-                            continue;
-                        }
-                    }
-                }
+    fn move_boids<V: BoidVector>(
+        time: &Time,
+        config: &BoidsConfig,
+        relations: &BoidGroupRelations,
+        grid_config: Option<&BoidGridConfig>,
+        boid_query: &mut Query<BoidMovementData>,
+        surroundings: &BoidSurroundings,
+        reports: &mut BoidReports,
+    ) {
+        let start = Instant::now();
+        reports.debug_data.boids.clear();
+        let focused_only = !reports.focus_query.is_empty();
+        let mut neighbours_found = 0;
 
-                let distance = transform
-                    .translation
-                    .distance(neighbour_transform.translation);
+        // Boids flock with the boids that share their parent, in the parent's local space,
+        // so their ranges and speeds are in the same units
+        let mut snapshots = HashMap::<Option<Entity>, (BoidFrame, BoidSnapshot<V>)>::new();
 
-                if distance <= boid.view_config.view_range {
-                    if distance < boid.view_config.protected_range {
-                        let normalized_diff =
-                            (neighbour_transform.translation - transform.translation).normalize();
-                        let strength = boid.view_config.protected_range - distance;
+        for (
+            transform,
+            boid,
+            _,
+            collision_group,
+            collision_groups,
+            _,
+            _,
+            _,
+            _,
+            _,
+            _,
+            parent,
+            _,
+            entity,
+        ) in boid_query.iter()
+        {
+            let collision_groups = BoidCollisionGroups::resolve(collision_groups, collision_group);
 
-                        separation_vector -= normalized_diff * strength;
-                    }
+            let (_, snapshot) = snapshots.entry(parent.map(Parent::get)).or_insert_with(|| {
+                (
+                    BoidFrame::of(parent, &surroundings.parents),
+                    BoidSnapshot::default(),
+                )
+            });
 
-                    average_velocity += neighbour_boid.velocity;
-                    average_position += neighbour_transform.translation;
-                    neighbouring_boids += 1;
-                }
-            }
+            snapshot.push(
+                &transform.translation,
+                &boid.velocity(),
+                boid,
+                collision_groups,
+                entity,
+            );
+        }
 
-            if neighbouring_boids > 0 {
-                average_velocity /= neighbouring_boids as f32;
-                average_position /= neighbouring_boids as f32;
+        let grid_start = Instant::now();
+        for (_, snapshot) in snapshots.values_mut() {
+            snapshot.build_grid(grid_config);
+        }
+        let grid_build_time = grid_start.elapsed();
+
+        for (
+            mut transform,
+            mut boid,
+            border,
+            collision_group,
+            collision_groups,
+            follow_leader,
+            path,
+            susceptibility,
+            altitude,
+            sdf_boundary,
+            whiskers,
+            parent,
+            (debug_info, focused),
+            entity,
+        ) in boid_query.iter_mut()
+        {
+            let collision_groups = BoidCollisionGroups::resolve(collision_groups, collision_group);
 
-                movement_vector +=
-                    (average_velocity - boid.velocity) * boid.turning_strength.alignment;
-                movement_vector +=
-                    (average_position - transform.translation) * boid.turning_strength.coherence;
+            let Some((frame, snapshot)) = snapshots.get_mut(&parent.map(Parent::get)) else {
+                continue;
+            };
+            let frame = *frame;
+            let world_position = frame.point_to_world(transform.translation);
+            let world_boid = frame.boid_to_world(&boid);
+
+            let flocking = snapshot.calc_flocking(
+                &transform.translation,
+                &boid,
+                collision_groups,
+                entity,
+                relations,
+            );
+
+            // Forces are added in order of priority, so that avoiding obstacles, other boids
+            // and borders wins over flocking when the boid's acceleration is limited.
+            let mut steering = BoidSteering::<V>::new(
+                boid.max_acceleration
+                    .map(|max_acceleration| max_acceleration * time.delta_seconds()),
+                &config.space,
+            );
+
+            if let Some(whiskers) = whiskers {
+                steering.add(
+                    BoidRule::Obstacles,
+                    frame.vector_to_local(whiskers.calc_avoidance(
+                        world_position,
+                        &world_boid,
+                        &surroundings.obstacles,
+                        &config.space,
+                    )),
+                );
             }
 
-            movement_vector += separation_vector * boid.turning_strength.separation;
+            steering.add(
+                BoidRule::Separation,
+                flocking.separation * boid.turning_strength.separation,
+            );
 
             if let Some(border) = border {
-                movement_vector += border.calc_avoidance(transform.translation, &boid);
+                steering.add(
+                    BoidRule::Border,
+                    border.calc_avoidance(transform.translation, &boid),
+                );
+            }
+
+            if let Some(sdf_boundary) = sdf_boundary {
+                steering.add(
+                    BoidRule::Border,
+                    sdf_boundary.calc_avoidance(transform.translation, &boid),
+                );
+            }
+
+            if let (Some(altitude), Some(terrain)) = (altitude, &surroundings.terrain) {
+                steering.add(
+                    BoidRule::Altitude,
+                    frame.vector_to_local(altitude.calc_steering(world_position, terrain)),
+                );
+            }
+
+            let leader = follow_leader
+                .and_then(|follow_leader| surroundings.leaders.get(follow_leader.0).ok());
+            if let Some(leader) = leader {
+                steering.add(
+                    BoidRule::Leader,
+                    frame.vector_to_local(leader.calc_evasion(world_position, &world_boid)),
+                );
+                steering.add(
+                    BoidRule::Leader,
+                    frame.vector_to_local(leader.calc_follow(world_position, &world_boid)),
+                );
+            }
+
+            if let Some(mut path) = path {
+                steering.add(
+                    BoidRule::Path,
+                    path.calc_steering(transform.translation, &boid),
+                );
+            }
+
+            if let Some(flow_field) = &surroundings.flow_field {
+                steering.add(
+                    BoidRule::FlowField,
+                    frame.vector_to_local(flow_field.calc_steering(world_position)),
+                );
+            }
+
+            steering.add(
+                BoidRule::Alignment,
+                flocking.alignment * boid.turning_strength.alignment,
+            );
+            steering.add(
+                BoidRule::Coherence,
+                flocking.coherence * boid.turning_strength.coherence,
+            );
+
+            if boid.turning_strength.wander != 0.0 {
+                steering.add(
+                    BoidRule::Wander,
+                    boid.wander.calc_force(
+                        boid.velocity(),
+                        entity.index(),
+                        time.elapsed_seconds(),
+                        &config.space,
+                    ) * boid.turning_strength.wander,
+                );
+            }
+
+            let neighbour_count = snapshot.neighbours.len();
+            neighbours_found += neighbour_count;
+
+            if let Some(mut debug_info) = debug_info {
+                debug_info.neighbours = neighbour_count;
+                debug_info.forces = steering.forces;
+            }
+
+            if config.debug && (focused || !focused_only) {
+                reports.debug_data.boids.insert(
+                    entity,
+                    BoidDebugRecord {
+                        forces: steering.forces,
+                        neighbours: snapshot
+                            .neighbour_positions()
+                            .map(|position| frame.point_to_world(position))
+                            .collect(),
+                    },
+                );
             }
 
-            let new_velocity = boid.velocity + movement_vector;
+            let previous_velocity = V::velocity_of(&boid);
+            let mut new_velocity = previous_velocity + steering.force;
 
-            boid.velocity = if new_velocity.length_squared() > 0.0 {
+            if let Some(max_turn_rate) = boid.max_turn_rate {
+                new_velocity = previous_velocity.limit_turn(
+                    new_velocity,
+                    max_turn_rate * time.delta_seconds(),
+                    &config.space,
+                );
+            }
+
+            let velocity = if new_velocity.length_squared() > 0.0 {
                 new_velocity.clamp_length(boid.speed.min, boid.speed.max)
             } else {
-                Vec3::X * boid.speed.min
+                let heading = match &config.space {
+                    BoidSpace::Plane(plane) => plane.normal.any_orthonormal_vector(),
+                    _ => Vec3::X,
+                };
+                V::from_vec3(&heading) * boid.speed.min
             };
+            boid.velocity = velocity.into_velocity();
+
+            if time.delta_seconds() > 0.0 {
+                boid.acceleration =
+                    ((velocity - previous_velocity) * time.delta_seconds().recip()).to_vec3();
+            }
+
+            // In 2d, this leaves the z layer as it is
+            transform.translation += (velocity * time.delta_seconds()).to_vec3();
+
+            if let Some(environment) = &surroundings.environment {
+                let susceptibility = susceptibility.copied().unwrap_or_default().0;
+                let wind = frame.vector_to_local(
+                    environment.velocity_at(world_position, time.elapsed_seconds()),
+                );
+
+                transform.translation +=
+                    config.space.project_vector(wind) * susceptibility * time.delta_seconds();
+            }
 
-            transform.translation += boid.velocity * time.delta_seconds();
+            if let BoidSpace::Plane(plane) = &config.space {
+                transform.translation = plane.project_point(transform.translation);
+            }
         }
-    }
 
-    pub(super) fn handle_boid_orientation(
-        mut boid_query: Query<(&mut Transform, &Boid)>,
-        config: Res<BoidsConfig>,
-    ) {
-        for (mut transform, boid) in boid_query.iter_mut() {
-            let forward = transform.forward();
+        let mut stats = BoidStats {
+            neighbours_found,
+            grid_build_time,
+            ..BoidStats::default()
+        };
+        reports.debug_data.grids.clear();
+
+        for (frame, snapshot) in snapshots.into_values() {
+            stats.boids += snapshot.boids.len();
+            stats.neighbours_checked += snapshot.checked;
+
+            if let Some(grid) = snapshot.grid {
+                stats.cell_size = stats.cell_size.max(grid.cell_size());
+                stats.occupied_cells += grid.cells().count();
 
-            match config.space {
-                BoidSpace::TwoDimensional => transform.look_to(*forward, boid.velocity.normalize()),
-                BoidSpace::ThreeDimensional => {
-                    transform.look_to(boid.velocity.normalize(), *forward)
+                if config.debug {
+                    reports.debug_data.grids.push((frame, grid));
                 }
             }
         }
+
+        stats.simulation_time = start.elapsed();
+        *reports.stats = stats;
     }
 
-    pub(super) fn handle_boid_gizmos(
+    pub(super) fn handle_boid_orientation(
+        time: Res<Time>,
+        mut boid_query: Query<(
+            &mut Transform,
+            &Boid,
+            Option<&BoidModelForward>,
+            Option<&BoidBanking>,
+            Option<&mut BoidOrientationSmoothing>,
+        )>,
         config: Res<BoidsConfig>,
-        boid_query: Query<(&Transform, &Boid)>,
-        mut gizmos: Gizmos,
     ) {
-        if config.debug {
-            match config.space {
-                BoidSpace::TwoDimensional => {
-                    for (transform, boid) in boid_query.iter() {
-                        for angle in
-                            -(boid.view_config.fov as i32 / 2)..boid.view_config.fov as i32 / 2
-                        {
-                            let angle_radians = (angle as f32).to_radians();
-
-                            gizmos.ray_2d(
-                                transform.translation.truncate(),
-                                transform
-                                    .up()
-                                    .truncate()
-                                    .rotate(Vec2::from_angle(angle_radians))
-                                    * boid.view_config.view_range,
-                                Color::Rgba {
-                                    red: 1.0,
-                                    green: 0.0,
-                                    blue: 0.0,
-                                    alpha: 0.01,
-                                },
-                            );
-                        }
-                    }
-                }
-                BoidSpace::ThreeDimensional => {
-                    for (transform, boid) in boid_query.iter() {
-                        for angle in
-                            -(boid.view_config.fov as i32) / 2..boid.view_config.fov as i32 / 2
-                        {
-                            let angle_radians = (angle as f32).to_radians();
-
-                            gizmos.ray(
-                                transform.translation,
-                                transform
-                                    .up()
-                                    .truncate()
-                                    .rotate(Vec2::from_angle(angle_radians))
-                                    .extend(0.0)
-                                    * boid.view_config.view_range,
-                                Color::Rgba {
-                                    red: 1.0,
-                                    green: 0.0,
-                                    blue: 0.0,
-                                    alpha: 0.1,
-                                },
-                            );
-                        }
-                    }
+        for (mut transform, boid, model_forward, banking, smoothing) in boid_query.iter_mut() {
+            let Some(direction) = boid.velocity().try_normalize() else {
+                continue;
+            };
+
+            // Orient the boid as if its model faced the default way, and then turn the model
+            // so its own forward axis lines up.
+            let offset = model_forward
+                .copied()
+                .unwrap_or_default()
+                .offset(&config.space);
+            let mut oriented = Transform::from_rotation(transform.rotation * offset.inverse());
+            let forward = oriented.forward();
+
+            match (&config.space, banking) {
+                (BoidSpace::TwoDimensional, _) => oriented.look_to(*forward, direction),
+                (_, Some(banking)) => {
+                    oriented.rotation = banking.calc_rotation(direction, boid.acceleration)
                 }
+                (BoidSpace::ThreeDimensional, None) => oriented.look_to(direction, *forward),
+                (BoidSpace::Plane(plane), None) => oriented.look_to(direction, plane.normal),
             }
+
+            let target = oriented.rotation * offset;
+
+            transform.rotation = match smoothing {
+                Some(mut smoothing) => {
+                    smoothing.smooth(transform.rotation, target, time.delta_seconds())
+                }
+                None => target,
+            };
         }
     }
 }
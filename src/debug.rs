@@ -0,0 +1,516 @@
+use bevy::{
+    prelude::{Color, Component, Entity, Resource, Vec3},
+    utils::HashMap,
+};
+
+use crate::{boids::BoidFrame, spatial::BoidGrid};
+
+/// Resource choosing which debug gizmos are drawn, when `debug` is enabled in the
+/// [BoidsConfig](crate::boids::BoidsConfig).
+///
+/// Without it, only the field of view is drawn.
+///
+/// ## Example
+/// ```rust,ignore
+/// app.insert_resource(BoidDebugGizmos {
+///     forces: true,
+///     neighbours: true,
+///     ..default()
+/// });
+/// ```
+#[derive(Resource, Clone, Copy)]
+pub struct BoidDebugGizmos {
+    /// Rays showing the field of view of each boid.
+    pub fov: bool,
+    /// Circles, or spheres in 3d, showing the protected and view range of each boid.
+    pub ranges: bool,
+    /// An arrow along the velocity of each boid.
+    pub velocity: bool,
+    /// An arrow for each steering force of each boid, coloured by its [BoidRule].
+    pub forces: bool,
+    /// Lines from each boid to the neighbours it flocked with last tick.
+    pub neighbours: bool,
+    /// The box of each [BoidBorder](crate::boids::BoidBorder), with its margins.
+    pub borders: bool,
+    /// The centroid of each flock, with a circle showing how spread out the flock is.
+    /// Boids with the same collision group memberships count as one flock.
+    pub centroids: bool,
+    /// The occupied cells of the grid boids are sorted into to find their neighbours.
+    /// Cells with more boids are drawn more opaque and red.
+    pub grid: bool,
+    /// How long the velocity arrows are, per unit of velocity.
+    pub velocity_scale: f32,
+    /// How long the force arrows are, per unit of force.
+    pub force_scale: f32,
+}
+
+impl Default for BoidDebugGizmos {
+    fn default() -> Self {
+        Self {
+            fov: true,
+            ranges: false,
+            velocity: false,
+            forces: false,
+            neighbours: false,
+            borders: false,
+            centroids: false,
+            grid: false,
+            velocity_scale: 0.25,
+            force_scale: 10.0,
+        }
+    }
+}
+
+/// The steering rules a boid follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BoidRule {
+    Obstacles,
+    Separation,
+    /// The [BoidBorder](crate::boids::BoidBorder) and
+    /// [BoidSdfBoundary](crate::sdf::BoidSdfBoundary).
+    Border,
+    Altitude,
+    Leader,
+    Path,
+    FlowField,
+    Alignment,
+    Coherence,
+    Wander,
+}
+
+impl BoidRule {
+    pub const ALL: [BoidRule; 10] = [
+        BoidRule::Obstacles,
+        BoidRule::Separation,
+        BoidRule::Border,
+        BoidRule::Altitude,
+        BoidRule::Leader,
+        BoidRule::Path,
+        BoidRule::FlowField,
+        BoidRule::Alignment,
+        BoidRule::Coherence,
+        BoidRule::Wander,
+    ];
+
+    /// The colour the rule's force is drawn with.
+    pub fn color(&self) -> Color {
+        match self {
+            BoidRule::Obstacles => Color::ORANGE_RED,
+            BoidRule::Separation => Color::RED,
+            BoidRule::Border => Color::YELLOW,
+            BoidRule::Altitude => Color::OLIVE,
+            BoidRule::Leader => Color::FUCHSIA,
+            BoidRule::Path => Color::VIOLET,
+            BoidRule::FlowField => Color::TEAL,
+            BoidRule::Alignment => Color::BLUE,
+            BoidRule::Coherence => Color::GREEN,
+            BoidRule::Wander => Color::GRAY,
+        }
+    }
+}
+
+/// The steering forces a boid's rules asked for last tick, in the boid's own space.
+///
+/// The forces include the turning strengths, but not the limit of
+/// [max_acceleration](crate::boids::Boid::max_acceleration).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoidForces {
+    pub obstacles: Vec3,
+    pub separation: Vec3,
+    pub border: Vec3,
+    pub altitude: Vec3,
+    pub leader: Vec3,
+    pub path: Vec3,
+    pub flow_field: Vec3,
+    pub alignment: Vec3,
+    pub coherence: Vec3,
+    pub wander: Vec3,
+}
+
+impl BoidForces {
+    pub fn get(&self, rule: BoidRule) -> Vec3 {
+        match rule {
+            BoidRule::Obstacles => self.obstacles,
+            BoidRule::Separation => self.separation,
+            BoidRule::Border => self.border,
+            BoidRule::Altitude => self.altitude,
+            BoidRule::Leader => self.leader,
+            BoidRule::Path => self.path,
+            BoidRule::FlowField => self.flow_field,
+            BoidRule::Alignment => self.alignment,
+            BoidRule::Coherence => self.coherence,
+            BoidRule::Wander => self.wander,
+        }
+    }
+
+    pub fn get_mut(&mut self, rule: BoidRule) -> &mut Vec3 {
+        match rule {
+            BoidRule::Obstacles => &mut self.obstacles,
+            BoidRule::Separation => &mut self.separation,
+            BoidRule::Border => &mut self.border,
+            BoidRule::Altitude => &mut self.altitude,
+            BoidRule::Leader => &mut self.leader,
+            BoidRule::Path => &mut self.path,
+            BoidRule::FlowField => &mut self.flow_field,
+            BoidRule::Alignment => &mut self.alignment,
+            BoidRule::Coherence => &mut self.coherence,
+            BoidRule::Wander => &mut self.wander,
+        }
+    }
+
+    /// Iterates over the force of each rule.
+    pub fn iter(&self) -> impl Iterator<Item = (BoidRule, Vec3)> + '_ {
+        BoidRule::ALL.into_iter().map(|rule| (rule, self.get(rule)))
+    }
+}
+
+/// Marker component that limits the detailed debug gizmos to the boids that have it.
+///
+/// While any boid has it, the field of view, ranges, velocity, forces and neighbours are
+/// only drawn for those boids. Borders and flock centroids are still drawn for every boid.
+#[derive(Component, Clone, Copy, Default)]
+pub struct BoidDebugFocus;
+
+/// Component the plugin fills with what the boid saw and did last tick, to show in an
+/// inspector or on-screen text.
+///
+/// It's filled whether debugging is enabled or not, so only add it to the boids you
+/// want to inspect.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct BoidDebugInfo {
+    /// How many neighbours the boid flocked with.
+    pub neighbours: usize,
+    /// The steering forces of each rule.
+    pub forces: BoidForces,
+}
+
+/// What each boid saw and did last tick, recorded by the movement system while debugging.
+#[derive(Resource, Default)]
+pub(crate) struct BoidDebugData {
+    pub(crate) boids: HashMap<Entity, BoidDebugRecord>,
+    /// The grids the boids were sorted into to find their neighbours, one for each space
+    /// boids flock in.
+    pub(crate) grids: Vec<(BoidFrame, BoidGrid)>,
+}
+
+#[derive(Default)]
+pub(crate) struct BoidDebugRecord {
+    pub(crate) forces: BoidForces,
+    /// The world space positions of the neighbours the boid flocked with.
+    pub(crate) neighbours: Vec<Vec3>,
+}
+
+pub(crate) mod systems {
+    use bevy::{
+        math::primitives::Direction3d,
+        prelude::{Gizmos, GlobalTransform, Has, Parent, Quat, Query, Res, Transform, Vec2, With},
+    };
+
+    use super::*;
+    use crate::boids::{
+        Boid, BoidBorder, BoidCollisionGroup, BoidCollisionGroups, BoidFrame, BoidSpace,
+        BoidsConfig,
+    };
+
+    pub(crate) fn handle_boid_gizmos(
+        config: Res<BoidsConfig>,
+        settings: Option<Res<BoidDebugGizmos>>,
+        data: Res<BoidDebugData>,
+        boid_query: Query<(
+            &Transform,
+            &Boid,
+            Option<&BoidBorder>,
+            Option<&BoidCollisionGroup>,
+            Option<&BoidCollisionGroups>,
+            Option<&Parent>,
+            Has<BoidDebugFocus>,
+            Entity,
+        )>,
+        parent_query: Query<&GlobalTransform>,
+        focus_query: Query<(), With<BoidDebugFocus>>,
+        mut gizmos: Gizmos,
+    ) {
+        if !config.debug {
+            return;
+        }
+
+        let focused_only = !focus_query.is_empty();
+
+        let settings = settings.map(|settings| *settings).unwrap_or_default();
+        let mut borders = Vec::new();
+        let mut flocks = HashMap::new();
+
+        for (transform, boid, border, collision_group, collision_groups, parent, focused, entity) in
+            boid_query.iter()
+        {
+            let frame = BoidFrame::of(parent, &parent_query);
+            let position = frame.point_to_world(transform.translation);
+            let velocity = frame.vector_to_world(boid.velocity());
+            let heading = velocity.try_normalize();
+            let detailed = focused || !focused_only;
+
+            if settings.fov && detailed {
+                if let Some(heading) = heading {
+                    draw_fov(&mut gizmos, &config.space, &frame, position, heading, boid);
+                }
+            }
+
+            if settings.ranges && detailed {
+                draw_ranges(&mut gizmos, &config.space, &frame, position, boid);
+            }
+
+            if settings.velocity && detailed && velocity != Vec3::ZERO {
+                gizmos.arrow(
+                    position,
+                    position + velocity * settings.velocity_scale,
+                    Color::WHITE,
+                );
+            }
+
+            if let Some(record) = data.boids.get(&entity) {
+                if settings.forces {
+                    for (rule, force) in record.forces.iter() {
+                        if force != Vec3::ZERO {
+                            let force = frame.vector_to_world(force) * settings.force_scale;
+                            gizmos.arrow(position, position + force, rule.color());
+                        }
+                    }
+                }
+
+                if settings.neighbours {
+                    for neighbour in &record.neighbours {
+                        let neighbour = match config.space {
+                            BoidSpace::TwoDimensional => neighbour.truncate().extend(position.z),
+                            _ => *neighbour,
+                        };
+                        gizmos.line(position, neighbour, Color::rgba(1.0, 1.0, 1.0, 0.2));
+                    }
+                }
+            }
+
+            if settings.borders {
+                if let Some(border) = border {
+                    let key = (parent.map(|parent| parent.get()), *border);
+                    if !borders.iter().any(|(other, _)| *other == key) {
+                        borders.push((key, frame));
+                    }
+                }
+            }
+
+            if settings.centroids {
+                let memberships = BoidCollisionGroups::resolve(collision_groups, collision_group)
+                    .unwrap_or_default()
+                    .memberships;
+                flocks
+                    .entry(memberships.bits())
+                    .or_insert_with(Vec::new)
+                    .push(position);
+            }
+        }
+
+        if settings.grid {
+            for (frame, grid) in &data.grids {
+                draw_grid(&mut gizmos, &config.space, frame, grid);
+            }
+        }
+
+        for ((_, border), frame) in borders {
+            draw_border(&mut gizmos, &config.space, &border, &frame);
+        }
+
+        for positions in flocks.values() {
+            let centroid = positions.iter().sum::<Vec3>() / positions.len() as f32;
+            let spread = positions
+                .iter()
+                .map(|position| position.distance(centroid))
+                .sum::<f32>()
+                / positions.len() as f32;
+
+            draw_circle(&mut gizmos, &config.space, centroid, spread, Color::CYAN);
+            draw_circle(&mut gizmos, &config.space, centroid, 2.0, Color::CYAN);
+        }
+    }
+
+    fn draw_fov(
+        gizmos: &mut Gizmos,
+        space: &BoidSpace,
+        frame: &BoidFrame,
+        position: Vec3,
+        heading: Vec3,
+        boid: &Boid,
+    ) {
+        let half_fov = boid.view_config.fov as i32 / 2;
+        let view_range = frame.length_to_world(boid.view_config.view_range);
+
+        match space {
+            BoidSpace::TwoDimensional => {
+                for angle in -half_fov..half_fov {
+                    let angle_radians = (angle as f32).to_radians();
+
+                    gizmos.ray_2d(
+                        position.truncate(),
+                        heading.truncate().rotate(Vec2::from_angle(angle_radians)) * view_range,
+                        Color::rgba(1.0, 0.0, 0.0, 0.01),
+                    );
+                }
+            }
+            BoidSpace::Plane(plane) => {
+                for angle in -half_fov..half_fov {
+                    let rotation = Quat::from_axis_angle(plane.normal, (angle as f32).to_radians());
+
+                    gizmos.ray(
+                        position,
+                        rotation * heading * view_range,
+                        Color::rgba(1.0, 0.0, 0.0, 0.05),
+                    );
+                }
+            }
+            BoidSpace::ThreeDimensional => {
+                // The edge of the view cone, seen as rays around the heading
+                const RAYS: u32 = 24;
+                let half_fov = (half_fov as f32).to_radians();
+                let side = heading.any_orthonormal_vector();
+
+                for ray in 0..RAYS {
+                    let around = Quat::from_axis_angle(
+                        heading,
+                        std::f32::consts::TAU * ray as f32 / RAYS as f32,
+                    );
+                    let rotation = Quat::from_axis_angle(around * side, half_fov);
+
+                    gizmos.ray(
+                        position,
+                        rotation * heading * view_range,
+                        Color::rgba(1.0, 0.0, 0.0, 0.1),
+                    );
+                }
+            }
+        }
+    }
+
+    fn draw_grid(gizmos: &mut Gizmos, space: &BoidSpace, frame: &BoidFrame, grid: &BoidGrid) {
+        let cell_size = grid.cell_size();
+        // 2d grids only have cells with a z of zero, so they're drawn flat
+        let size = match space {
+            BoidSpace::TwoDimensional => Vec3::new(cell_size, cell_size, 0.0),
+            _ => Vec3::splat(cell_size),
+        };
+        let max = grid.cells().map(|(_, count)| count).max().unwrap_or(1) as f32;
+
+        for (cell, count) in grid.cells() {
+            let occupancy = count as f32 / max;
+            let color = Color::rgba(1.0, 1.0 - occupancy, 0.0, 0.1 + occupancy * 0.6);
+            let min = cell.as_vec3() * cell_size;
+
+            draw_box(gizmos, frame, min, min + size, color);
+        }
+    }
+
+    fn draw_ranges(
+        gizmos: &mut Gizmos,
+        space: &BoidSpace,
+        frame: &BoidFrame,
+        position: Vec3,
+        boid: &Boid,
+    ) {
+        draw_circle(
+            gizmos,
+            space,
+            position,
+            frame.length_to_world(boid.view_config.protected_range),
+            Color::rgba(1.0, 0.3, 0.3, 0.5),
+        );
+        draw_circle(
+            gizmos,
+            space,
+            position,
+            frame.length_to_world(boid.view_config.view_range),
+            Color::rgba(0.3, 1.0, 0.3, 0.3),
+        );
+    }
+
+    /// Draws a circle in 2d and on planes, and a sphere in 3d.
+    fn draw_circle(
+        gizmos: &mut Gizmos,
+        space: &BoidSpace,
+        position: Vec3,
+        radius: f32,
+        color: Color,
+    ) {
+        match space {
+            BoidSpace::TwoDimensional => {
+                gizmos.circle_2d(position.truncate(), radius, color);
+            }
+            BoidSpace::ThreeDimensional => {
+                gizmos.sphere(position, Quat::IDENTITY, radius, color);
+            }
+            BoidSpace::Plane(plane) => {
+                let normal = Direction3d::new(plane.normal).unwrap_or(Direction3d::Y);
+                gizmos.circle(position, normal, radius, color);
+            }
+        }
+    }
+
+    /// Draws the box of `border`, and the box its margins start at.
+    ///
+    /// Sides that aren't set are drawn at the opposite side, or at zero.
+    fn draw_border(gizmos: &mut Gizmos, space: &BoidSpace, border: &BoidBorder, frame: &BoidFrame) {
+        fn side(side: Option<(f32, f32)>, opposite: Option<(f32, f32)>) -> (f32, f32) {
+            side.or(opposite.map(|(position, _)| (position, 0.0)))
+                .unwrap_or((0.0, 0.0))
+        }
+
+        let (left, left_margin) = side(border.left, border.right);
+        let (right, right_margin) = side(border.right, border.left);
+        let (bottom, bottom_margin) = side(border.bottom, border.top);
+        let (top, top_margin) = side(border.top, border.bottom);
+        let (front, front_margin) = side(border.front, border.back);
+        let (back, back_margin) = side(border.back, border.front);
+
+        let (min, max, margin_min, margin_max) = match space {
+            BoidSpace::TwoDimensional => (
+                Vec3::new(left, bottom, 0.0),
+                Vec3::new(right, top, 0.0),
+                Vec3::new(left + left_margin, bottom + bottom_margin, 0.0),
+                Vec3::new(right - right_margin, top - top_margin, 0.0),
+            ),
+            _ => (
+                Vec3::new(left, bottom, front),
+                Vec3::new(right, top, back),
+                Vec3::new(
+                    left + left_margin,
+                    bottom + bottom_margin,
+                    front + front_margin,
+                ),
+                Vec3::new(right - right_margin, top - top_margin, back - back_margin),
+            ),
+        };
+
+        draw_box(gizmos, frame, min, max, Color::YELLOW);
+        draw_box(
+            gizmos,
+            frame,
+            margin_min,
+            margin_max,
+            Color::rgba(1.0, 1.0, 0.0, 0.3),
+        );
+    }
+
+    fn draw_box(gizmos: &mut Gizmos, frame: &BoidFrame, min: Vec3, max: Vec3, color: Color) {
+        let corner = |x: bool, y: bool, z: bool| {
+            frame.point_to_world(Vec3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            ))
+        };
+
+        for a in [false, true] {
+            for b in [false, true] {
+                gizmos.line(corner(false, a, b), corner(true, a, b), color);
+                gizmos.line(corner(a, false, b), corner(a, true, b), color);
+                gizmos.line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+}
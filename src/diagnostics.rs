@@ -0,0 +1,112 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::{IntoSystemConfigs, Plugin, Res, Resource, Update},
+    utils::Duration,
+};
+
+use crate::boids::BoidSet;
+
+/// Plugin that reports the cost of the boid simulation as Bevy [Diagnostic]s, so it can
+/// be logged with `LogDiagnosticsPlugin`, or tracked in production builds.
+///
+/// The measurements are taken from [BoidStats].
+///
+/// ## Example
+/// ```rust,ignore
+/// app.add_plugins((
+///     BoidsPlugin,
+///     BoidDiagnosticsPlugin,
+///     LogDiagnosticsPlugin::default(),
+/// ));
+/// ```
+pub struct BoidDiagnosticsPlugin;
+
+impl BoidDiagnosticsPlugin {
+    /// How many boids were simulated.
+    pub const BOID_COUNT: DiagnosticPath = DiagnosticPath::const_new("boids/count");
+    /// How long the movement system took, in milliseconds.
+    pub const SIMULATION_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/simulation_time");
+    /// How many boids were checked for being neighbours, summed over all boids.
+    pub const NEIGHBOUR_CHECKS: DiagnosticPath =
+        DiagnosticPath::const_new("boids/neighbour_checks");
+    /// The average amount of neighbours each boid flocked with.
+    pub const AVERAGE_NEIGHBOURS: DiagnosticPath =
+        DiagnosticPath::const_new("boids/average_neighbours");
+    /// How long sorting the boids into the grid took, in milliseconds.
+    pub const GRID_BUILD_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/grid_build_time");
+}
+
+impl Plugin for BoidDiagnosticsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.register_diagnostic(Diagnostic::new(Self::BOID_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::SIMULATION_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::NEIGHBOUR_CHECKS))
+            .register_diagnostic(Diagnostic::new(Self::AVERAGE_NEIGHBOURS))
+            .register_diagnostic(Diagnostic::new(Self::GRID_BUILD_TIME).with_suffix("ms"))
+            .add_systems(
+                Update,
+                systems::report_boid_diagnostics.after(BoidSet::Movement),
+            );
+    }
+}
+
+/// Resource with statistics about the last tick of the simulation, updated every tick.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct BoidStats {
+    /// How many boids were simulated.
+    pub boids: usize,
+    /// The size of the grid cells, or zero if the grid wasn't used. Boids parented to
+    /// different entities are sorted into separate grids, and this is the largest of their
+    /// cell sizes.
+    pub cell_size: f32,
+    /// How many grid cells had boids in them.
+    pub occupied_cells: usize,
+    /// How many boids were checked for being neighbours, summed over all boids.
+    pub neighbours_checked: usize,
+    /// How many of the checked boids were neighbours, summed over all boids.
+    pub neighbours_found: usize,
+    /// How long the movement system took.
+    pub simulation_time: Duration,
+    /// How long sorting the boids into the grid took.
+    pub grid_build_time: Duration,
+}
+
+impl BoidStats {
+    /// The average amount of boids each boid checked for being a neighbour.
+    pub fn average_checked(&self) -> f32 {
+        if self.boids == 0 {
+            return 0.0;
+        }
+
+        self.neighbours_checked as f32 / self.boids as f32
+    }
+
+    /// The average amount of neighbours each boid flocked with.
+    pub fn average_neighbours(&self) -> f32 {
+        if self.boids == 0 {
+            return 0.0;
+        }
+
+        self.neighbours_found as f32 / self.boids as f32
+    }
+}
+
+mod systems {
+    use super::*;
+
+    pub(super) fn report_boid_diagnostics(stats: Res<BoidStats>, mut diagnostics: Diagnostics) {
+        diagnostics.add_measurement(&BoidDiagnosticsPlugin::BOID_COUNT, || stats.boids as f64);
+        diagnostics.add_measurement(&BoidDiagnosticsPlugin::SIMULATION_TIME, || {
+            stats.simulation_time.as_secs_f64() * 1000.0
+        });
+        diagnostics.add_measurement(&BoidDiagnosticsPlugin::NEIGHBOUR_CHECKS, || {
+            stats.neighbours_checked as f64
+        });
+        diagnostics.add_measurement(&BoidDiagnosticsPlugin::AVERAGE_NEIGHBOURS, || {
+            stats.average_neighbours() as f64
+        });
+        diagnostics.add_measurement(&BoidDiagnosticsPlugin::GRID_BUILD_TIME, || {
+            stats.grid_build_time.as_secs_f64() * 1000.0
+        });
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::{Component, Resource, Vec3};
+
+use crate::boids::noise;
+
+/// Resource describing the wind or currents that boids drift with.
+///
+/// Unlike the steering behaviours, drift doesn't change the boid's velocity. It displaces
+/// the boid directly, so fish drift with currents and birds get blown off course, no
+/// matter where they are heading. How much each boid drifts is set with [BoidSusceptibility].
+///
+/// ## Example
+/// ```rust,ignore
+/// app.insert_resource(BoidEnvironment {
+///     wind: BoidWind::new(Vec3::new(40.0, 0.0, 0.0)).with_gusts(Vec3::new(30.0, 10.0, 0.0), 0.2),
+///     volumes: vec![BoidWindVolume::new(
+///         Vec3::new(-200.0, -1000.0, -1.0),
+///         Vec3::new(200.0, 1000.0, 1.0),
+///         BoidWind::new(Vec3::new(0.0, -150.0, 0.0)),
+///     )],
+/// });
+/// ```
+#[derive(Resource, Clone, Default)]
+pub struct BoidEnvironment {
+    /// The wind that blows everywhere.
+    pub wind: BoidWind,
+    /// Winds that only blow within a volume, on top of the global wind.
+    pub volumes: Vec<BoidWindVolume>,
+}
+
+impl BoidEnvironment {
+    pub fn new(wind: BoidWind) -> Self {
+        Self {
+            wind,
+            volumes: Vec::new(),
+        }
+    }
+
+    pub fn with_volume(mut self, volume: BoidWindVolume) -> Self {
+        self.volumes.push(volume);
+        self
+    }
+
+    /// The velocity of the wind at `position`, at `time` seconds.
+    pub fn velocity_at(&self, position: Vec3, time: f32) -> Vec3 {
+        self.volumes
+            .iter()
+            .filter(|volume| volume.contains(position))
+            .fold(self.wind.velocity_at(time), |velocity, volume| {
+                velocity + volume.wind.velocity_at(time)
+            })
+    }
+}
+
+/// A wind or current, with optional gusts.
+#[derive(Clone, Copy, Default)]
+pub struct BoidWind {
+    /// The steady velocity of the wind.
+    pub velocity: Vec3,
+    /// The largest velocity gusts add to, or remove from, the steady velocity along each axis.
+    pub gust: Vec3,
+    /// How often the gusts change, in cycles per second.
+    pub gust_frequency: f32,
+}
+
+impl BoidWind {
+    pub fn new(velocity: Vec3) -> Self {
+        Self {
+            velocity,
+            gust: Vec3::ZERO,
+            gust_frequency: 0.0,
+        }
+    }
+
+    pub fn with_gusts(mut self, gust: Vec3, frequency: f32) -> Self {
+        self.gust = gust;
+        self.gust_frequency = frequency;
+        self
+    }
+
+    /// The velocity of the wind at `time` seconds, including gusts.
+    pub fn velocity_at(&self, time: f32) -> Vec3 {
+        if self.gust == Vec3::ZERO {
+            return self.velocity;
+        }
+
+        let t = time * self.gust_frequency;
+
+        self.velocity + self.gust * Vec3::new(noise(0, 0, t), noise(0, 1, t), noise(0, 2, t))
+    }
+}
+
+/// A box shaped volume where an extra wind blows.
+#[derive(Clone, Copy)]
+pub struct BoidWindVolume {
+    /// The corner of the volume with the smallest coordinates.
+    pub min: Vec3,
+    /// The corner of the volume with the largest coordinates.
+    pub max: Vec3,
+    pub wind: BoidWind,
+}
+
+impl BoidWindVolume {
+    pub fn new(min: Vec3, max: Vec3, wind: BoidWind) -> Self {
+        Self { min, max, wind }
+    }
+
+    pub fn contains(&self, position: Vec3) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+}
+
+/// Component setting how much a boid drifts with the [BoidEnvironment].
+///
+/// `0.0` means the boid isn't affected at all, and `1.0` means the boid moves with the
+/// full velocity of the wind. Boids without this component have a susceptibility of `1.0`.
+#[derive(Component, Clone, Copy)]
+pub struct BoidSusceptibility(pub f32);
+
+impl Default for BoidSusceptibility {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
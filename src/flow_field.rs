@@ -0,0 +1,172 @@
+use bevy::prelude::{Resource, UVec3, Vec3};
+
+/// Resource with a grid of vectors covering a region, that boids steer along.
+///
+/// Use it to drive flocks with precomputed wind, river currents or pathfinding fields.
+/// The vectors are sampled with trilinear interpolation, and the sampled vector is added
+/// to the boid's steering, scaled by `weight`. Boids outside the region aren't affected.
+///
+/// The vectors sit on the corners of the grid cells, so a field of `size` vectors covers
+/// `(size - 1) * cell_size` units along each axis. For 2d flocks, use a `size.z` of `1`,
+/// and the field will ignore the z axis.
+///
+/// ## Example
+/// ```rust,ignore
+/// // A whirlpool around the origin
+/// app.insert_resource(BoidFlowField::from_fn(
+///     Vec3::new(-1000.0, -1000.0, 0.0),
+///     50.0,
+///     UVec3::new(41, 41, 1),
+///     0.5,
+///     |position| Vec3::new(-position.y, position.x, 0.0).normalize_or_zero() * 100.0,
+/// ));
+/// ```
+#[derive(Resource, Clone)]
+pub struct BoidFlowField {
+    origin: Vec3,
+    cell_size: f32,
+    size: UVec3,
+    vectors: Vec<Vec3>,
+    /// How strongly boids steer along the field.
+    pub weight: f32,
+}
+
+impl BoidFlowField {
+    /// Creates a field where every vector is zero.
+    ///
+    /// `origin` is the position of the first vector, in the corner with the smallest coordinates.
+    pub fn new(origin: Vec3, cell_size: f32, size: UVec3, weight: f32) -> Self {
+        Self::from_vectors(
+            origin,
+            cell_size,
+            size,
+            vec![Vec3::ZERO; (size.x * size.y * size.z) as usize],
+            weight,
+        )
+    }
+
+    /// Creates a field by calling `f` with the position of each vector.
+    pub fn from_fn(
+        origin: Vec3,
+        cell_size: f32,
+        size: UVec3,
+        weight: f32,
+        mut f: impl FnMut(Vec3) -> Vec3,
+    ) -> Self {
+        let mut field = Self::new(origin, cell_size, size, weight);
+
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let cell = UVec3::new(x, y, z);
+                    field.set(cell, f(field.position(cell)));
+                }
+            }
+        }
+
+        field
+    }
+
+    /// Creates a field from a list of vectors, ordered by x first, then y, then z.
+    ///
+    /// # Panics
+    /// Panics if there isn't exactly one vector for each point in the grid.
+    pub fn from_vectors(
+        origin: Vec3,
+        cell_size: f32,
+        size: UVec3,
+        vectors: Vec<Vec3>,
+        weight: f32,
+    ) -> Self {
+        assert_eq!(
+            vectors.len(),
+            (size.x * size.y * size.z) as usize,
+            "the flow field needs exactly one vector for each point in the grid"
+        );
+
+        Self {
+            origin,
+            cell_size,
+            size,
+            vectors,
+            weight,
+        }
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.origin
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// The position of the vector at `cell`.
+    pub fn position(&self, cell: UVec3) -> Vec3 {
+        self.origin + cell.as_vec3() * self.cell_size
+    }
+
+    pub fn get(&self, cell: UVec3) -> Vec3 {
+        self.vectors[self.index(cell)]
+    }
+
+    pub fn set(&mut self, cell: UVec3, vector: Vec3) {
+        let index = self.index(cell);
+        self.vectors[index] = vector;
+    }
+
+    /// Samples the field at `position` with trilinear interpolation.
+    ///
+    /// Returns `None` if `position` is outside of the field.
+    pub fn sample(&self, position: Vec3) -> Option<Vec3> {
+        if self.vectors.is_empty() {
+            return None;
+        }
+
+        let local = (position - self.origin) / self.cell_size;
+        let max = (self.size - UVec3::ONE).as_vec3();
+
+        // Axes with a single vector are flat, and ignored.
+        let flat = self.size.cmpeq(UVec3::ONE);
+        let local = Vec3::select(flat, Vec3::ZERO, local);
+
+        if local.cmplt(Vec3::ZERO).any() || local.cmpgt(max).any() {
+            return None;
+        }
+
+        let cell = local.floor().min(max).as_uvec3();
+        let next = (cell + UVec3::ONE).min(self.size - UVec3::ONE);
+        let t = local - cell.as_vec3();
+
+        let corner = |x: bool, y: bool, z: bool| {
+            self.get(UVec3::new(
+                if x { next.x } else { cell.x },
+                if y { next.y } else { cell.y },
+                if z { next.z } else { cell.z },
+            ))
+        };
+
+        let x00 = corner(false, false, false).lerp(corner(true, false, false), t.x);
+        let x10 = corner(false, true, false).lerp(corner(true, true, false), t.x);
+        let x01 = corner(false, false, true).lerp(corner(true, false, true), t.x);
+        let x11 = corner(false, true, true).lerp(corner(true, true, true), t.x);
+
+        let y0 = x00.lerp(x10, t.y);
+        let y1 = x01.lerp(x11, t.y);
+
+        Some(y0.lerp(y1, t.z))
+    }
+
+    /// Calculates the force that steers a boid at `position` along the field.
+    pub(crate) fn calc_steering(&self, position: Vec3) -> Vec3 {
+        self.sample(position).unwrap_or(Vec3::ZERO) * self.weight
+    }
+
+    fn index(&self, cell: UVec3) -> usize {
+        (cell.x + cell.y * self.size.x + cell.z * self.size.x * self.size.y) as usize
+    }
+}
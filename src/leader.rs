@@ -0,0 +1,132 @@
+use bevy::prelude::{Component, Entity, Vec3};
+
+use crate::boids::Boid;
+
+/// Component that makes an entity a leader, which boids with [FollowLeader] steer after.
+/// The leader doesn't have to be a boid itself, so it can for example be the player.
+///
+/// Followers steer towards a spot `follow_distance` behind the leader, and get out of the
+/// lane in front of it, so they don't block its path. Followers keep flocking on top of this.
+///
+/// ## Example
+/// ```rust,ignore
+/// let leader = commands.spawn((SpatialBundle::default(), BoidLeader::new(60.0, 40.0, 0.1))).id();
+///
+/// commands.spawn((
+///    MaterialMesh2dBundle::default(),
+///    Boid::new(speed, turning_strength, view_config),
+///    FollowLeader(leader),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy)]
+pub struct BoidLeader {
+    /// How far behind the leader its followers try to stay.
+    pub follow_distance: f32,
+    /// The half-width of the lane in front of the leader, that followers get out of.
+    /// The lane is as long as `follow_distance`.
+    pub clearance: f32,
+    /// How strongly followers steer after the leader, and out of its way.
+    pub strength: f32,
+    position: Vec3,
+    velocity: Vec3,
+    tracked: bool,
+}
+
+impl BoidLeader {
+    pub fn new(follow_distance: f32, clearance: f32, strength: f32) -> Self {
+        Self {
+            follow_distance,
+            clearance,
+            strength,
+            position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            tracked: false,
+        }
+    }
+
+    /// The leader's velocity, estimated from how it moved last tick.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Calculates the force that steers `boid` out of the leader's way.
+    pub(crate) fn calc_evasion(&self, position: Vec3, boid: &Boid) -> Vec3 {
+        let Some(heading) = self.velocity.try_normalize() else {
+            return Vec3::ZERO;
+        };
+        if !self.in_view(position, boid) {
+            return Vec3::ZERO;
+        }
+
+        let offset = position - self.position;
+        let ahead = offset.dot(heading);
+        if ahead < 0.0 || ahead > self.follow_distance {
+            return Vec3::ZERO;
+        }
+
+        let lateral = offset - heading * ahead;
+        let lateral_distance = lateral.length();
+        if lateral_distance >= self.clearance {
+            return Vec3::ZERO;
+        }
+
+        let direction = lateral
+            .try_normalize()
+            .unwrap_or_else(|| heading.any_orthonormal_vector());
+        let strength = 1.0 - lateral_distance / self.clearance;
+
+        direction * boid.speed.max * strength * self.strength
+    }
+
+    /// Calculates the force that steers `boid` towards the spot behind the leader.
+    pub(crate) fn calc_follow(&self, position: Vec3, boid: &Boid) -> Vec3 {
+        if !self.in_view(position, boid) {
+            return Vec3::ZERO;
+        }
+
+        let behind = self.position
+            - self.velocity.try_normalize().unwrap_or(Vec3::ZERO) * self.follow_distance;
+
+        // Match the leader's velocity, and close the remaining distance to the spot behind
+        // it within about a second.
+        let desired_velocity =
+            (self.velocity + (behind - position)).clamp_length_max(boid.speed.max);
+
+        (desired_velocity - boid.velocity()) * self.strength
+    }
+
+    fn in_view(&self, position: Vec3, boid: &Boid) -> bool {
+        self.tracked && position.distance(self.position) <= boid.view_config.view_range
+    }
+}
+
+/// Component that makes a boid follow a [BoidLeader].
+///
+/// If the leader is despawned or out of view, the boid falls back to normal flocking.
+#[derive(Component, Clone, Copy)]
+pub struct FollowLeader(pub Entity);
+
+pub(crate) mod systems {
+    use bevy::{
+        prelude::{GlobalTransform, Query, Res},
+        time::Time,
+    };
+
+    use super::*;
+
+    pub(crate) fn track_boid_leaders(
+        time: Res<Time>,
+        mut leader_query: Query<(&mut BoidLeader, &GlobalTransform)>,
+    ) {
+        for (mut leader, transform) in leader_query.iter_mut() {
+            let position = transform.translation();
+
+            if leader.tracked && time.delta_seconds() > 0.0 {
+                leader.velocity = (position - leader.position) / time.delta_seconds();
+            }
+
+            leader.position = position;
+            leader.tracked = true;
+        }
+    }
+}
@@ -1,6 +1,31 @@
 #[allow(clippy::type_complexity)]
 pub mod boids;
+#[allow(clippy::type_complexity)]
+pub mod debug;
+pub mod diagnostics;
+pub mod environment;
+pub mod flow_field;
+pub mod leader;
+#[allow(clippy::type_complexity)]
+pub mod obstacles;
+pub mod path;
+#[allow(clippy::type_complexity)]
+pub mod recording;
+pub mod sdf;
+pub mod spatial;
+pub mod terrain;
 
 pub mod prelude {
     pub use super::boids::*;
+    pub use super::debug::*;
+    pub use super::diagnostics::*;
+    pub use super::environment::*;
+    pub use super::flow_field::*;
+    pub use super::leader::*;
+    pub use super::obstacles::*;
+    pub use super::path::*;
+    pub use super::recording::*;
+    pub use super::sdf::*;
+    pub use super::spatial::*;
+    pub use super::terrain::*;
 }
@@ -0,0 +1,474 @@
+use bevy::prelude::{Component, Entity, Mat3, Quat, Resource, Vec3};
+
+use crate::boids::{Boid, BoidFrame, BoidSpace};
+
+/// Marker component for static level meshes that boids with [BoidWhiskers] avoid.
+///
+/// The entity needs a [Handle<Mesh>](bevy::prelude::Handle), or a
+/// [Mesh2dHandle](bevy::sprite::Mesh2dHandle) in 2d, and a
+/// [Transform](bevy::prelude::Transform). The plugin builds a bounding volume hierarchy of
+/// all obstacle triangles, which is rebuilt whenever an obstacle is added, moved or removed.
+///
+/// Obstacles with a [Parent](bevy::prelude::Parent) get a hierarchy in their parent's
+/// local space, so obstacles on a moving ship are carried along without being rebuilt.
+///
+/// In 2d, the triangles are flattened onto the XY plane, so the boids avoid the outline
+/// of the mesh whatever their z layer is.
+#[derive(Component, Clone, Copy, Default)]
+pub struct BoidObstacle;
+
+/// Component that makes a boid avoid [obstacles](BoidObstacle) by casting rays.
+///
+/// Each tick, the boid casts a ray along its velocity. If it hits an obstacle, the boid
+/// tries rays in more and more different directions within `spread`, and steers towards
+/// the first one that is clear.
+#[derive(Component, Clone, Copy)]
+pub struct BoidWhiskers {
+    /// How many directions the boid tries, including straight ahead.
+    pub rays: u32,
+    /// How far ahead the boid looks for obstacles.
+    pub length: f32,
+    /// The largest angle from the velocity the boid tries, in radians.
+    pub spread: f32,
+    /// How strongly the boid steers towards the clear direction.
+    pub strength: f32,
+}
+
+impl BoidWhiskers {
+    pub fn new(rays: u32, length: f32, spread: f32, strength: f32) -> Self {
+        Self {
+            rays,
+            length,
+            spread,
+            strength,
+        }
+    }
+
+    /// Calculates the force that steers `boid` towards the first clear direction.
+    pub(crate) fn calc_avoidance(
+        &self,
+        position: Vec3,
+        boid: &Boid,
+        obstacles: &BoidObstacleBvh,
+        space: &BoidSpace,
+    ) -> Vec3 {
+        let Some(heading) = space.project_vector(boid.velocity()).try_normalize() else {
+            return Vec3::ZERO;
+        };
+
+        if obstacles.cast_ray(position, heading, self.length).is_none() {
+            return Vec3::ZERO;
+        }
+
+        let rotation = match space {
+            BoidSpace::TwoDimensional => Quat::from_rotation_arc(Vec3::Y, heading),
+            // Line the 2d fan's +Y up with the heading, and its +Z with the normal
+            BoidSpace::Plane(plane) => Quat::from_mat3(&Mat3::from_cols(
+                heading.cross(plane.normal),
+                heading,
+                plane.normal,
+            )),
+            BoidSpace::ThreeDimensional => Quat::from_rotation_arc(Vec3::Z, heading),
+        };
+
+        let clear_direction = self
+            .directions(space)
+            .map(|direction| rotation * direction)
+            .find(|direction| {
+                obstacles
+                    .cast_ray(position, *direction, self.length)
+                    .is_none()
+            });
+
+        // When every direction is blocked, turn around
+        let direction = clear_direction.unwrap_or(-heading);
+
+        (direction * boid.speed.max - boid.velocity()) * self.strength
+    }
+
+    /// The directions to try after straight ahead, ordered by their angle from it.
+    ///
+    /// In 2d and on planes, straight ahead is +Y, and the directions fan out to alternating sides.
+    /// In 3d, straight ahead is +Z, and the directions follow a golden spiral around it.
+    fn directions<'a>(&'a self, space: &'a BoidSpace) -> impl Iterator<Item = Vec3> + 'a {
+        let rays = self.rays.max(2);
+
+        (1..rays).map(move |ray| match space {
+            BoidSpace::TwoDimensional | BoidSpace::Plane(_) => {
+                let step = ray.div_ceil(2);
+                let side = if ray % 2 == 1 { 1.0 } else { -1.0 };
+                let angle = side * self.spread * step as f32 / (rays / 2).max(1) as f32;
+
+                Quat::from_rotation_z(angle) * Vec3::Y
+            }
+            BoidSpace::ThreeDimensional => {
+                let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+                let polar = self.spread * (ray as f32 / (rays - 1) as f32).sqrt();
+                let azimuth = golden_angle * ray as f32;
+
+                Vec3::new(
+                    polar.sin() * azimuth.cos(),
+                    polar.sin() * azimuth.sin(),
+                    polar.cos(),
+                )
+            }
+        })
+    }
+}
+
+/// Resource with the bounding volume hierarchies of every [BoidObstacle]'s triangles.
+///
+/// It's built by the plugin, but can also be used to cast rays against the obstacles yourself.
+#[derive(Resource, Default)]
+pub struct BoidObstacleBvh {
+    /// A hierarchy for the obstacles without a parent, and one for the obstacles of each
+    /// parent, in the parent's local space.
+    spaces: Vec<BvhSpace>,
+    /// Whether the triangles are flattened onto z = 0, and rays are cast from there.
+    flat: bool,
+}
+
+struct BvhSpace {
+    parent: Option<Entity>,
+    frame: BoidFrame,
+    triangles: Vec<[Vec3; 3]>,
+    nodes: Vec<BvhNode>,
+}
+
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    /// For leaves, the first triangle. Otherwise the index of the second child, as the
+    /// first child always comes right after its parent.
+    start: u32,
+    /// The amount of triangles in a leaf, or zero for other nodes.
+    count: u32,
+}
+
+impl BoidObstacleBvh {
+    const MAX_LEAF_TRIANGLES: usize = 4;
+
+    /// Builds a hierarchy of world space triangles.
+    pub fn new(triangles: Vec<[Vec3; 3]>) -> Self {
+        let mut bvh = Self::default();
+        bvh.add_space(None, triangles);
+        bvh
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.spaces.iter().map(|space| space.triangles.len()).sum()
+    }
+
+    /// Casts a ray from `origin` along `direction`, which has to be normalized.
+    ///
+    /// Returns the distance to the closest triangle hit within `max_distance`.
+    pub fn cast_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        self.spaces
+            .iter()
+            .filter_map(|space| {
+                let mut local_origin = space.frame.point_to_local(origin);
+                let local_direction = space.frame.vector_to_local(direction);
+                if self.flat {
+                    local_origin.z = 0.0;
+                }
+
+                // Distances are scaled along with the parent
+                let scale = local_direction.length();
+                if scale <= 0.0 {
+                    return None;
+                }
+
+                space
+                    .cast_ray(local_origin, local_direction / scale, max_distance * scale)
+                    .map(|distance| distance / scale)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    fn add_space(&mut self, parent: Option<Entity>, mut triangles: Vec<[Vec3; 3]>) {
+        if self.flat {
+            for vertex in triangles.iter_mut().flatten() {
+                vertex.z = 0.0;
+            }
+        }
+
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            let count = triangles.len();
+            Self::build(&mut triangles, &mut nodes, 0, count);
+        }
+
+        self.spaces.push(BvhSpace {
+            parent,
+            frame: BoidFrame::WORLD,
+            triangles,
+            nodes,
+        });
+    }
+
+    fn build(
+        triangles: &mut [[Vec3; 3]],
+        nodes: &mut Vec<BvhNode>,
+        offset: usize,
+        count: usize,
+    ) -> usize {
+        let slice = &mut triangles[offset..offset + count];
+
+        let (min, max) = slice.iter().flatten().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), vertex| (min.min(*vertex), max.max(*vertex)),
+        );
+
+        let index = nodes.len();
+        nodes.push(BvhNode {
+            min,
+            max,
+            start: offset as u32,
+            count: count as u32,
+        });
+
+        if count <= Self::MAX_LEAF_TRIANGLES {
+            return index;
+        }
+
+        // Split at the median centroid, along the longest axis
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let centroid = |triangle: &[Vec3; 3]| (triangle[0] + triangle[1] + triangle[2])[axis];
+
+        let half = count / 2;
+        slice.select_nth_unstable_by(half, |a, b| centroid(a).total_cmp(&centroid(b)));
+
+        Self::build(triangles, nodes, offset, half);
+        let second = Self::build(triangles, nodes, offset + half, count - half);
+
+        nodes[index].start = second as u32;
+        nodes[index].count = 0;
+
+        index
+    }
+}
+
+impl BvhSpace {
+    fn cast_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = direction.recip();
+        let mut closest = None;
+        let mut max_distance = max_distance;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !ray_hits_box(origin, inverse_direction, node.min, node.max, max_distance) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(index + 1);
+                stack.push(node.start as usize);
+                continue;
+            }
+
+            let triangles =
+                &self.triangles[node.start as usize..(node.start + node.count) as usize];
+            for triangle in triangles {
+                if let Some(distance) = ray_hits_triangle(origin, direction, triangle) {
+                    if distance <= max_distance {
+                        max_distance = distance;
+                        closest = Some(distance);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+fn ray_hits_box(
+    origin: Vec3,
+    inverse_direction: Vec3,
+    min: Vec3,
+    max: Vec3,
+    max_distance: f32,
+) -> bool {
+    let t1 = (min - origin) * inverse_direction;
+    let t2 = (max - origin) * inverse_direction;
+
+    let near = t1.min(t2).max_element().max(0.0);
+    let far = t1.max(t2).min_element().min(max_distance);
+
+    near <= far
+}
+
+/// Möller–Trumbore ray-triangle intersection. Hits from both sides of the triangle.
+///
+/// Rays that lie in the plane of the triangle, like the rays of 2d boids against flat
+/// meshes, hit the triangle's edges instead.
+fn ray_hits_triangle(origin: Vec3, direction: Vec3, [a, b, c]: &[Vec3; 3]) -> Option<f32> {
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return ray_hits_triangle_edges(origin, direction, [*a, *b, *c]);
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - *a;
+
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inverse_determinant;
+
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Intersects a ray lying in the plane of a triangle with the triangle's edges.
+///
+/// Returns `None` if the ray is off the plane, or the triangle is degenerate.
+fn ray_hits_triangle_edges(origin: Vec3, direction: Vec3, triangle: [Vec3; 3]) -> Option<f32> {
+    const PLANE_TOLERANCE: f32 = 1e-3;
+
+    let normal = (triangle[1] - triangle[0])
+        .cross(triangle[2] - triangle[0])
+        .try_normalize()?;
+    if (origin - triangle[0]).dot(normal).abs() > PLANE_TOLERANCE {
+        return None;
+    }
+
+    let mut closest: Option<f32> = None;
+
+    for (index, start) in triangle.iter().enumerate() {
+        let edge = triangle[(index + 1) % 3] - *start;
+
+        // Solve origin + direction * distance = start + edge * along, within the plane
+        let denominator = direction.cross(edge).dot(normal);
+        if denominator.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let to_start = *start - origin;
+        let distance = to_start.cross(edge).dot(normal) / denominator;
+        let along = to_start.cross(direction).dot(normal) / denominator;
+
+        if distance >= 0.0 && (0.0..=1.0).contains(&along) {
+            closest = Some(closest.map_or(distance, |closest| closest.min(distance)));
+        }
+    }
+
+    closest
+}
+
+pub(crate) mod systems {
+    use bevy::{
+        prelude::{
+            Added, Assets, Changed, DetectChanges, GlobalTransform, Handle, Local, Mesh, Or,
+            Parent, Query, RemovedComponents, Res, ResMut, Transform, With,
+        },
+        sprite::Mesh2dHandle,
+        utils::HashMap,
+    };
+
+    use super::*;
+    use crate::{boids::BoidsConfig, terrain::mesh_triangles};
+
+    pub(crate) fn rebuild_boid_obstacles(
+        mut bvh: ResMut<BoidObstacleBvh>,
+        config: Res<BoidsConfig>,
+        meshes: Res<Assets<Mesh>>,
+        obstacle_query: Query<
+            (
+                Option<&Handle<Mesh>>,
+                Option<&Mesh2dHandle>,
+                &Transform,
+                Option<&Parent>,
+            ),
+            With<BoidObstacle>,
+        >,
+        changed_query: Query<
+            (),
+            (
+                With<BoidObstacle>,
+                Or<(
+                    Added<BoidObstacle>,
+                    Changed<Transform>,
+                    Changed<Parent>,
+                    Changed<Handle<Mesh>>,
+                    Changed<Mesh2dHandle>,
+                )>,
+            ),
+        >,
+        mut removed: (RemovedComponents<BoidObstacle>, RemovedComponents<Parent>),
+        mut pending: Local<bool>,
+    ) {
+        // Moving a parent doesn't change its obstacles' local transforms, so it doesn't
+        // rebuild anything
+        let removed = removed.0.read().count() > 0 || removed.1.read().count() > 0;
+        if changed_query.is_empty() && !removed && !*pending && !config.is_changed() {
+            return;
+        }
+
+        // Meshes that haven't loaded yet are picked up by a later rebuild
+        *pending = false;
+        let mut spaces = HashMap::<Option<Entity>, Vec<[Vec3; 3]>>::new();
+
+        for (mesh, mesh_2d, transform, parent) in obstacle_query.iter() {
+            let Some(handle) = mesh.or(mesh_2d.map(|mesh_2d| &mesh_2d.0)) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(handle) else {
+                *pending = true;
+                continue;
+            };
+
+            if let Some(mesh_triangles) = mesh_triangles(mesh, *transform) {
+                spaces
+                    .entry(parent.map(Parent::get))
+                    .or_default()
+                    .extend(mesh_triangles);
+            }
+        }
+
+        *bvh = BoidObstacleBvh {
+            flat: matches!(config.space, BoidSpace::TwoDimensional),
+            ..BoidObstacleBvh::default()
+        };
+        for (parent, triangles) in spaces {
+            bvh.add_space(parent, triangles);
+        }
+    }
+
+    /// Moves the hierarchies of parented obstacles along with their parents.
+    pub(crate) fn update_boid_obstacle_frames(
+        mut bvh: ResMut<BoidObstacleBvh>,
+        parent_query: Query<&GlobalTransform>,
+    ) {
+        for space in &mut bvh.spaces {
+            if let Some(parent) = space.parent {
+                space.frame = parent_query
+                    .get(parent)
+                    .map_or(BoidFrame::WORLD, BoidFrame::new);
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::{
+    math::cubic_splines::CubicCurve,
+    prelude::{Component, Vec3},
+};
+
+use crate::boids::Boid;
+
+/// Component that makes a boid follow a path of waypoints, while still flocking.
+///
+/// The boid predicts where it will be shortly, and steers back onto the path if that
+/// position is further than `radius` from it. While on the path, it steers along it,
+/// progressing from waypoint to waypoint.
+///
+/// When the boid reaches the end of an open path, it goes back to just flocking.
+/// A closed path loops forever.
+///
+/// ## Example
+/// ```rust,ignore
+/// commands.spawn((
+///    MaterialMesh2dBundle::default(),
+///    Boid::new(speed, turning_strength, view_config),
+///    BoidPath::closed(
+///       vec![
+///          Vec3::new(-500.0, -300.0, 0.0),
+///          Vec3::new(500.0, -300.0, 0.0),
+///          Vec3::new(0.0, 400.0, 0.0),
+///       ],
+///       80.0,
+///       0.1,
+///    ),
+/// ));
+/// ```
+#[derive(Component, Clone)]
+pub struct BoidPath {
+    points: Vec<Vec3>,
+    closed: bool,
+    /// How far from the path the boid can be before it steers back onto it.
+    pub radius: f32,
+    /// How strongly the boid steers along the path.
+    pub strength: f32,
+    /// How far ahead in time the boid predicts its position, in seconds.
+    pub prediction: f32,
+    segment: usize,
+    finished: bool,
+}
+
+impl BoidPath {
+    /// Creates an open path, that goes from the first point to the last.
+    pub fn new(points: impl Into<Vec<Vec3>>, radius: f32, strength: f32) -> Self {
+        Self {
+            points: points.into(),
+            closed: false,
+            radius,
+            strength,
+            prediction: 0.25,
+            segment: 0,
+            finished: false,
+        }
+    }
+
+    /// Creates a closed path, that loops back from the last point to the first.
+    pub fn closed(points: impl Into<Vec<Vec3>>, radius: f32, strength: f32) -> Self {
+        Self {
+            closed: true,
+            ..Self::new(points, radius, strength)
+        }
+    }
+
+    /// Creates an open path along a curve, split into `subdivisions` segments.
+    pub fn from_curve(
+        curve: &CubicCurve<Vec3>,
+        subdivisions: usize,
+        radius: f32,
+        strength: f32,
+    ) -> Self {
+        Self::new(
+            curve.iter_positions(subdivisions).collect::<Vec<_>>(),
+            radius,
+            strength,
+        )
+    }
+
+    pub fn with_prediction(mut self, prediction: f32) -> Self {
+        self.prediction = prediction;
+        self
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// The index of the waypoint the boid is currently heading towards.
+    pub fn next_waypoint(&self) -> usize {
+        (self.segment + 1) % self.points.len().max(1)
+    }
+
+    /// Whether the boid has reached the end of an open path.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Starts the path over from the first waypoint.
+    pub fn restart(&mut self) {
+        self.segment = 0;
+        self.finished = false;
+    }
+
+    fn segment_count(&self) -> usize {
+        match self.points.len() {
+            0 | 1 => 0,
+            count if self.closed => count,
+            count => count - 1,
+        }
+    }
+
+    fn segment_points(&self, segment: usize) -> (Vec3, Vec3) {
+        (
+            self.points[segment],
+            self.points[(segment + 1) % self.points.len()],
+        )
+    }
+
+    /// Calculates the force that steers `boid` along the path, and advances the boid's
+    /// progress along it.
+    pub(crate) fn calc_steering(&mut self, position: Vec3, boid: &Boid) -> Vec3 {
+        let segment_count = self.segment_count();
+        if segment_count == 0 || self.finished {
+            return Vec3::ZERO;
+        }
+
+        // Move on to the next waypoint once the boid is past the current segment
+        loop {
+            let (start, end) = self.segment_points(self.segment);
+            if project(position, start, end).1 < 1.0 {
+                break;
+            }
+
+            if self.segment + 1 < segment_count {
+                self.segment += 1;
+            } else if self.closed {
+                self.segment = 0;
+                break;
+            } else {
+                self.finished = true;
+                return Vec3::ZERO;
+            }
+        }
+
+        let (start, end) = self.segment_points(self.segment);
+        let direction = (end - start).normalize_or_zero();
+
+        let predicted = position + boid.velocity() * self.prediction;
+        let (normal_point, _) = project(predicted, start, end);
+
+        let desired_velocity = if predicted.distance(normal_point) > self.radius {
+            let target = normal_point + direction * self.radius;
+            (target - position).normalize_or_zero() * boid.speed.max
+        } else {
+            direction * boid.speed.max
+        };
+
+        (desired_velocity - boid.velocity()) * self.strength
+    }
+}
+
+/// Projects `point` onto the segment from `start` to `end`.
+///
+/// Returns the closest point on the segment, and how far along the segment the
+/// projection is, where values outside `[0, 1]` are before or past the segment.
+fn project(point: Vec3, start: Vec3, end: Vec3) -> (Vec3, f32) {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return (start, 1.0);
+    }
+
+    let t = (point - start).dot(segment) / length_squared;
+
+    (start + segment * t.clamp(0.0, 1.0), t)
+}
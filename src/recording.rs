@@ -0,0 +1,451 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::{
+    log::error,
+    prelude::{
+        resource_exists, Entity, IntoSystemConfigs, Plugin, Query, ResMut, Resource, Transform,
+        Update, Vec3,
+    },
+};
+
+use crate::boids::{Boid, BoidCollisionGroup, BoidCollisionGroups, BoidSet};
+
+const BINARY_MAGIC: &[u8; 4] = b"BVRC";
+const BINARY_VERSION: u8 = 1;
+const CSV_HEADER: &str = "tick,id,px,py,pz,vx,vy,vz,group";
+/// The most records reserved up front for a binary frame, before they're actually read.
+const MAX_RESERVED_RECORDS: u32 = 4096;
+
+/// The plugin for recording boid trajectories to a file, and playing them back.
+///
+/// Recording starts when a [BoidRecorder] resource is inserted, and playback starts when a
+/// [BoidPlayback] resource is inserted. While playing back, the boids' movement systems are
+/// disabled, and the boids are driven by the file instead.
+///
+/// ## Example
+/// ```rust,ignore
+/// App::new()
+///     .add_plugins((DefaultPlugins, BoidsPlugin, BoidRecordingPlugin))
+///     .insert_resource(BoidRecorder::create("boids.bin", BoidRecordFormat::Binary).unwrap())
+///     .run();
+/// ```
+pub struct BoidRecordingPlugin;
+
+impl Plugin for BoidRecordingPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            (
+                systems::handle_boid_recording
+                    .run_if(resource_exists::<BoidRecorder>)
+                    .after(BoidSet::Movement),
+                systems::handle_boid_playback
+                    .run_if(resource_exists::<BoidPlayback>)
+                    .in_set(BoidSet::Movement),
+            ),
+        );
+    }
+}
+
+/// The file formats a recording can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoidRecordFormat {
+    /// Human readable, with one row per boid per tick.
+    Csv,
+    /// Compact little-endian binary, 32 bytes per boid per tick.
+    Binary,
+}
+
+/// The recorded state of a single boid in a single tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoidRecord {
+    /// The id of the boid. This is the [index](Entity::index) of the boid's entity, so
+    /// boids have to be spawned in the same order when playing back.
+    pub id: u32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// The bits of the [collision groups](BoidCollisionGroups) the boid is a member of.
+    pub group: u32,
+}
+
+/// All of the boid records of a single tick.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoidRecordFrame {
+    pub tick: u64,
+    pub boids: Vec<BoidRecord>,
+}
+
+/// Resource that records every boid's position, velocity and collision group each tick.
+///
+/// Remove the resource to stop recording. The file is flushed when the resource is dropped.
+#[derive(Resource)]
+pub struct BoidRecorder {
+    writer: BufWriter<File>,
+    format: BoidRecordFormat,
+    tick: u64,
+}
+
+impl BoidRecorder {
+    /// Creates the file at `path`, truncating it if it already exists.
+    pub fn create(path: impl AsRef<Path>, format: BoidRecordFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        match format {
+            BoidRecordFormat::Csv => writeln!(writer, "{CSV_HEADER}")?,
+            BoidRecordFormat::Binary => {
+                writer.write_all(BINARY_MAGIC)?;
+                writer.write_all(&[BINARY_VERSION])?;
+            }
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            tick: 0,
+        })
+    }
+
+    /// The amount of ticks that have been recorded so far.
+    pub fn ticks(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write_frame(&mut self, boids: &[BoidRecord]) -> io::Result<()> {
+        match self.format {
+            BoidRecordFormat::Csv => {
+                for boid in boids {
+                    writeln!(
+                        self.writer,
+                        "{},{},{},{},{},{},{},{},{}",
+                        self.tick,
+                        boid.id,
+                        boid.position.x,
+                        boid.position.y,
+                        boid.position.z,
+                        boid.velocity.x,
+                        boid.velocity.y,
+                        boid.velocity.z,
+                        boid.group,
+                    )?;
+                }
+            }
+            BoidRecordFormat::Binary => {
+                self.writer.write_all(&self.tick.to_le_bytes())?;
+                self.writer.write_all(&(boids.len() as u32).to_le_bytes())?;
+
+                for boid in boids {
+                    self.writer.write_all(&boid.id.to_le_bytes())?;
+                    for value in boid.position.to_array() {
+                        self.writer.write_all(&value.to_le_bytes())?;
+                    }
+                    for value in boid.velocity.to_array() {
+                        self.writer.write_all(&value.to_le_bytes())?;
+                    }
+                    self.writer.write_all(&boid.group.to_le_bytes())?;
+                }
+            }
+        }
+
+        self.tick += 1;
+
+        Ok(())
+    }
+}
+
+/// Resource that drives the boids from a recording, instead of simulating them.
+///
+/// Each tick, the next frame of the recording is applied to the boids' [Transform]s and
+/// velocities. When the recording ends, the boids stay put unless `looping` is set.
+#[derive(Resource)]
+pub struct BoidPlayback {
+    frames: Vec<BoidRecordFrame>,
+    current: usize,
+    /// Whether to start over when the recording ends.
+    pub looping: bool,
+}
+
+impl BoidPlayback {
+    /// Reads a recording from `path`. The format is detected from the file's contents.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let frames = if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+            read_binary(reader)?
+        } else {
+            read_csv(reader)?
+        };
+
+        Ok(Self::from_frames(frames))
+    }
+
+    pub fn from_frames(frames: Vec<BoidRecordFrame>) -> Self {
+        Self {
+            frames,
+            current: 0,
+            looping: false,
+        }
+    }
+
+    pub fn frames(&self) -> &[BoidRecordFrame] {
+        &self.frames
+    }
+
+    /// Whether every frame has been played, and the playback isn't looping.
+    pub fn finished(&self) -> bool {
+        !self.looping && self.current >= self.frames.len()
+    }
+
+    fn next_frame(&mut self) -> Option<&BoidRecordFrame> {
+        if self.looping && self.current >= self.frames.len() {
+            self.current = 0;
+        }
+
+        let frame = self.frames.get(self.current)?;
+        self.current += 1;
+
+        Some(frame)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_csv(reader: impl BufRead) -> io::Result<Vec<BoidRecordFrame>> {
+    let mut frames: Vec<BoidRecordFrame> = Vec::new();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line == CSV_HEADER {
+            continue;
+        }
+
+        let fields = line.split(',').collect::<Vec<_>>();
+        if fields.len() != 9 {
+            return Err(invalid_data(format!(
+                "line {}: expected 9 fields, found {}",
+                line_number + 1,
+                fields.len()
+            )));
+        }
+
+        let field = |index: usize| {
+            fields[index]
+                .parse::<f32>()
+                .map_err(|_| invalid_data(format!("line {}: invalid number", line_number + 1)))
+        };
+        let int_field = |index: usize| {
+            fields[index]
+                .parse::<u32>()
+                .map_err(|_| invalid_data(format!("line {}: invalid number", line_number + 1)))
+        };
+
+        let tick = fields[0]
+            .parse::<u64>()
+            .map_err(|_| invalid_data(format!("line {}: invalid tick", line_number + 1)))?;
+        let record = BoidRecord {
+            id: int_field(1)?,
+            position: Vec3::new(field(2)?, field(3)?, field(4)?),
+            velocity: Vec3::new(field(5)?, field(6)?, field(7)?),
+            group: int_field(8)?,
+        };
+
+        match frames.last_mut() {
+            Some(frame) if frame.tick == tick => frame.boids.push(record),
+            _ => frames.push(BoidRecordFrame {
+                tick,
+                boids: vec![record],
+            }),
+        }
+    }
+
+    Ok(frames)
+}
+
+fn read_binary(mut reader: impl Read) -> io::Result<Vec<BoidRecordFrame>> {
+    fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_vec3(reader: &mut impl Read) -> io::Result<Vec3> {
+        let x = f32::from_bits(read_u32(reader)?);
+        let y = f32::from_bits(read_u32(reader)?);
+        let z = f32::from_bits(read_u32(reader)?);
+        Ok(Vec3::new(x, y, z))
+    }
+
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if header[4] != BINARY_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported recording version {}",
+            header[4]
+        )));
+    }
+
+    let mut frames = Vec::new();
+
+    loop {
+        let mut tick = [0; 8];
+        match reader.read_exact(&mut tick) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+
+        // The count comes from the file, so a corrupt one only reserves a bounded amount
+        let count = read_u32(&mut reader)?;
+        let mut boids = Vec::with_capacity(count.min(MAX_RESERVED_RECORDS) as usize);
+        for _ in 0..count {
+            boids.push(BoidRecord {
+                id: read_u32(&mut reader)?,
+                position: read_vec3(&mut reader)?,
+                velocity: read_vec3(&mut reader)?,
+                group: read_u32(&mut reader)?,
+            });
+        }
+
+        frames.push(BoidRecordFrame {
+            tick: u64::from_le_bytes(tick),
+            boids,
+        });
+    }
+
+    Ok(frames)
+}
+
+mod systems {
+    use bevy::utils::HashMap;
+
+    use super::*;
+
+    pub(super) fn handle_boid_recording(
+        mut recorder: ResMut<BoidRecorder>,
+        boid_query: Query<(
+            &Transform,
+            &Boid,
+            Option<&BoidCollisionGroup>,
+            Option<&BoidCollisionGroups>,
+            Entity,
+        )>,
+    ) {
+        let mut boids = boid_query
+            .iter()
+            .map(
+                |(transform, boid, collision_group, collision_groups, entity)| BoidRecord {
+                    id: entity.index(),
+                    position: transform.translation,
+                    velocity: boid.velocity(),
+                    group: BoidCollisionGroups::resolve(collision_groups, collision_group)
+                        .unwrap_or_default()
+                        .memberships
+                        .bits(),
+                },
+            )
+            .collect::<Vec<_>>();
+        boids.sort_unstable_by_key(|boid| boid.id);
+
+        if let Err(error) = recorder.write_frame(&boids) {
+            error!("Failed to record boids: {error}");
+        }
+    }
+
+    pub(super) fn handle_boid_playback(
+        mut playback: ResMut<BoidPlayback>,
+        mut boid_query: Query<(&mut Transform, &mut Boid, Entity)>,
+    ) {
+        let Some(frame) = playback.next_frame() else {
+            return;
+        };
+
+        let records = frame
+            .boids
+            .iter()
+            .map(|record| (record.id, record))
+            .collect::<HashMap<_, _>>();
+
+        for (mut transform, mut boid, entity) in boid_query.iter_mut() {
+            if let Some(record) = records.get(&entity.index()) {
+                transform.translation = record.position;
+                boid.set_velocity(record.velocity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> Vec<BoidRecordFrame> {
+        (0..3)
+            .map(|tick| BoidRecordFrame {
+                tick,
+                boids: (0..4)
+                    .map(|id| BoidRecord {
+                        id,
+                        position: Vec3::new(id as f32 * 1.5, tick as f32 - 0.25, -3.125),
+                        velocity: Vec3::new(0.1, -20.0, id as f32 / 3.0),
+                        group: 1 << id,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn round_trip(format: BoidRecordFormat, name: &str) -> Vec<BoidRecordFrame> {
+        let path = std::env::temp_dir().join(format!("bevoids-{}-{name}", std::process::id()));
+
+        let mut recorder = BoidRecorder::create(&path, format).unwrap();
+        for frame in frames() {
+            recorder.write_frame(&frame.boids).unwrap();
+        }
+        drop(recorder);
+
+        let playback = BoidPlayback::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        playback.frames
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        assert_eq!(round_trip(BoidRecordFormat::Csv, "recording.csv"), frames());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        assert_eq!(
+            round_trip(BoidRecordFormat::Binary, "recording.bin"),
+            frames()
+        );
+    }
+
+    #[test]
+    fn csv_rejects_out_of_range_ids() {
+        let csv = format!("{CSV_HEADER}\n0,4294967296,0,0,0,0,0,0,1\n");
+        let error = read_csv(csv.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn binary_rejects_truncated_frames() {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(BINARY_VERSION);
+        bytes.extend_from_slice(&0_u64.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = read_binary(bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use bevy::prelude::{Component, UVec3, Vec3};
+
+use crate::boids::Boid;
+
+/// A signed distance function, describing a volume of any shape.
+///
+/// The distance is negative inside the volume, and positive outside it. Volumes are built
+/// from primitives, combined with [union](BoidSdf::union), [subtraction](BoidSdf::subtract)
+/// and [intersection](BoidSdf::intersect). Complex volumes can be [baked](BoidSdfGrid::bake)
+/// into a grid, to make them cheaper to sample.
+///
+/// ## Example
+/// ```rust,ignore
+/// // A tank with a rock in the middle
+/// let tank = BoidSdf::cuboid(Vec3::ZERO, Vec3::new(600.0, 300.0, 400.0))
+///     .subtract(BoidSdf::sphere(Vec3::new(0.0, -300.0, 0.0), 150.0));
+/// ```
+#[derive(Clone)]
+pub enum BoidSdf {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Cuboid {
+        center: Vec3,
+        half_size: Vec3,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: f32,
+    },
+    /// Everything below the plane through `point`, facing along `normal`.
+    HalfSpace {
+        point: Vec3,
+        normal: Vec3,
+    },
+    /// Everything that's in either volume.
+    Union(Box<BoidSdf>, Box<BoidSdf>),
+    /// Everything in the first volume, that isn't in the second.
+    Subtraction(Box<BoidSdf>, Box<BoidSdf>),
+    /// Everything that's in both volumes.
+    Intersection(Box<BoidSdf>, Box<BoidSdf>),
+    /// A baked grid of distances.
+    Grid(Arc<BoidSdfGrid>),
+}
+
+impl BoidSdf {
+    pub fn sphere(center: Vec3, radius: f32) -> Self {
+        Self::Sphere { center, radius }
+    }
+
+    pub fn cuboid(center: Vec3, half_size: Vec3) -> Self {
+        Self::Cuboid { center, half_size }
+    }
+
+    pub fn capsule(start: Vec3, end: Vec3, radius: f32) -> Self {
+        Self::Capsule { start, end, radius }
+    }
+
+    pub fn half_space(point: Vec3, normal: Vec3) -> Self {
+        Self::HalfSpace {
+            point,
+            normal: normal.normalize_or_zero(),
+        }
+    }
+
+    pub fn union(self, other: BoidSdf) -> Self {
+        Self::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtract(self, other: BoidSdf) -> Self {
+        Self::Subtraction(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersect(self, other: BoidSdf) -> Self {
+        Self::Intersection(Box::new(self), Box::new(other))
+    }
+
+    /// The signed distance from `point` to the surface of the volume.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        match self {
+            BoidSdf::Sphere { center, radius } => point.distance(*center) - radius,
+            BoidSdf::Cuboid { center, half_size } => {
+                let q = (point - *center).abs() - *half_size;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+            }
+            BoidSdf::Capsule { start, end, radius } => {
+                let segment = *end - *start;
+                let t = ((point - *start).dot(segment)
+                    / segment.length_squared().max(f32::EPSILON))
+                .clamp(0.0, 1.0);
+                point.distance(*start + segment * t) - radius
+            }
+            BoidSdf::HalfSpace {
+                point: plane_point,
+                normal,
+            } => (point - *plane_point).dot(*normal),
+            BoidSdf::Union(a, b) => a.distance(point).min(b.distance(point)),
+            BoidSdf::Subtraction(a, b) => a.distance(point).max(-b.distance(point)),
+            BoidSdf::Intersection(a, b) => a.distance(point).max(b.distance(point)),
+            BoidSdf::Grid(grid) => grid.distance(point),
+        }
+    }
+
+    /// The direction the distance grows the fastest in at `point`, which points out of the
+    /// volume near its surface.
+    pub fn gradient(&self, point: Vec3) -> Vec3 {
+        const EPSILON: f32 = 0.01;
+
+        let dx = Vec3::X * EPSILON;
+        let dy = Vec3::Y * EPSILON;
+        let dz = Vec3::Z * EPSILON;
+
+        Vec3::new(
+            self.distance(point + dx) - self.distance(point - dx),
+            self.distance(point + dy) - self.distance(point - dy),
+            self.distance(point + dz) - self.distance(point - dz),
+        )
+        .normalize_or_zero()
+    }
+}
+
+/// A grid of signed distances, sampled with trilinear interpolation.
+///
+/// Outside of the grid, the distance is estimated from the nearest edge of the grid.
+pub struct BoidSdfGrid {
+    origin: Vec3,
+    cell_size: f32,
+    size: UVec3,
+    distances: Vec<f32>,
+}
+
+impl BoidSdfGrid {
+    /// Creates a grid from a list of distances, ordered by x first, then y, then z.
+    ///
+    /// `origin` is the position of the first distance, in the corner with the smallest coordinates.
+    ///
+    /// # Panics
+    /// Panics if there isn't exactly one distance for each point in the grid, or the grid
+    /// is empty.
+    pub fn new(origin: Vec3, cell_size: f32, size: UVec3, distances: Vec<f32>) -> Self {
+        assert!(size.cmpgt(UVec3::ZERO).all(), "the sdf grid can't be empty");
+        assert_eq!(
+            distances.len(),
+            (size.x * size.y * size.z) as usize,
+            "the sdf grid needs exactly one distance for each point in the grid"
+        );
+
+        Self {
+            origin,
+            cell_size,
+            size,
+            distances,
+        }
+    }
+
+    /// Bakes `sdf` into a grid, by sampling it at each point of the grid.
+    pub fn bake(sdf: &BoidSdf, origin: Vec3, cell_size: f32, size: UVec3) -> Self {
+        let mut distances = Vec::with_capacity((size.x * size.y * size.z) as usize);
+
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let position = origin + UVec3::new(x, y, z).as_vec3() * cell_size;
+                    distances.push(sdf.distance(position));
+                }
+            }
+        }
+
+        Self::new(origin, cell_size, size, distances)
+    }
+
+    pub fn distance(&self, point: Vec3) -> f32 {
+        let max = (self.size - UVec3::ONE).as_vec3();
+        let local = (point - self.origin) / self.cell_size;
+        let clamped = local.clamp(Vec3::ZERO, max);
+        let outside = (local - clamped).length() * self.cell_size;
+
+        let cell = clamped.floor().min(max).as_uvec3();
+        let next = (cell + UVec3::ONE).min(self.size - UVec3::ONE);
+        let t = clamped - cell.as_vec3();
+
+        let corner = |x: bool, y: bool, z: bool| {
+            self.get(UVec3::new(
+                if x { next.x } else { cell.x },
+                if y { next.y } else { cell.y },
+                if z { next.z } else { cell.z },
+            ))
+        };
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let x00 = lerp(corner(false, false, false), corner(true, false, false), t.x);
+        let x10 = lerp(corner(false, true, false), corner(true, true, false), t.x);
+        let x01 = lerp(corner(false, false, true), corner(true, false, true), t.x);
+        let x11 = lerp(corner(false, true, true), corner(true, true, true), t.x);
+
+        lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z) + outside
+    }
+
+    fn get(&self, cell: UVec3) -> f32 {
+        self.distances
+            [(cell.x + cell.y * self.size.x + cell.z * self.size.x * self.size.y) as usize]
+    }
+}
+
+/// Whether a [BoidSdfBoundary] keeps boids inside or outside of its volume.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoidSdfMode {
+    /// The boids stay inside the volume.
+    #[default]
+    Contain,
+    /// The boids stay outside the volume.
+    Avoid,
+}
+
+/// Component that keeps a boid inside, or outside, a volume of any shape.
+///
+/// This works like [BoidBorder](crate::boids::BoidBorder), but for the volume of a
+/// [BoidSdf]. Once the boid is within `margin` of the surface, it steers away from it,
+/// along the gradient of the distance. How strongly is set by the `border` field of
+/// [BoidTurningStrength](crate::boids::BoidTurningStrength).
+#[derive(Component, Clone)]
+pub struct BoidSdfBoundary {
+    pub sdf: Arc<BoidSdf>,
+    pub mode: BoidSdfMode,
+    /// How far from the surface the boid starts turning.
+    pub margin: f32,
+}
+
+impl BoidSdfBoundary {
+    /// Keeps the boid inside of `sdf`.
+    pub fn contain(sdf: impl Into<Arc<BoidSdf>>, margin: f32) -> Self {
+        Self {
+            sdf: sdf.into(),
+            mode: BoidSdfMode::Contain,
+            margin,
+        }
+    }
+
+    /// Keeps the boid outside of `sdf`.
+    pub fn avoid(sdf: impl Into<Arc<BoidSdf>>, margin: f32) -> Self {
+        Self {
+            sdf: sdf.into(),
+            mode: BoidSdfMode::Avoid,
+            margin,
+        }
+    }
+
+    pub(crate) fn calc_avoidance(&self, position: Vec3, boid: &Boid) -> Vec3 {
+        let distance = self.sdf.distance(position);
+        let margin = self.margin.max(f32::EPSILON);
+
+        // How far into the margin the boid is, allowing overflow for increased strength.
+        let (normalized_point, direction) = match self.mode {
+            BoidSdfMode::Contain => ((distance + margin) / margin, -1.0),
+            BoidSdfMode::Avoid => ((margin - distance) / margin, 1.0),
+        };
+
+        if normalized_point <= 0.0 {
+            return Vec3::ZERO;
+        }
+
+        self.sdf.gradient(position) * direction * boid.turning_strength.border * normalized_point
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::{IVec3, Resource, Vec3},
+    utils::HashMap,
+};
+
+/// Resource configuring the grid boids are sorted into, so they only check the boids in
+/// nearby cells for neighbours.
+///
+/// Without it, the cells are as large as the largest `view_range` of all boids, so each
+/// boid checks the cells around its own. Smaller cells mean fewer boids checked, but more
+/// cells to look through. Use the grid debug gizmos and [BoidStats](crate::diagnostics::BoidStats)
+/// to tune it.
+#[derive(Resource, Clone, Copy, Default)]
+pub struct BoidGridConfig {
+    /// The size of the grid cells. `None` uses the largest `view_range` of all boids.
+    pub cell_size: Option<f32>,
+}
+
+/// A uniform grid of boid indices, hashed by cell.
+#[derive(Default)]
+pub(crate) struct BoidGrid {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<usize>>,
+}
+
+impl BoidGrid {
+    /// Sorts `positions` into cells of `cell_size`.
+    ///
+    /// Returns `None` if the cell size is unusable, like zero or infinite.
+    pub(crate) fn new(cell_size: f32, positions: impl Iterator<Item = Vec3>) -> Option<Self> {
+        if !cell_size.is_finite() || cell_size <= 0.0 {
+            return None;
+        }
+
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+
+        for (index, position) in positions.enumerate() {
+            grid.cells
+                .entry(grid.cell(position))
+                .or_default()
+                .push(index);
+        }
+
+        Some(grid)
+    }
+
+    pub(crate) fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The occupied cells, and how many boids are in each.
+    pub(crate) fn cells(&self) -> impl Iterator<Item = (IVec3, usize)> + '_ {
+        self.cells.iter().map(|(cell, boids)| (*cell, boids.len()))
+    }
+
+    /// Collects the boids in every cell that overlaps the box around `center`, into `out`.
+    ///
+    /// With `flat`, only the cells with a z of zero are checked.
+    pub(crate) fn query(&self, center: Vec3, radius: f32, flat: bool, out: &mut Vec<usize>) {
+        let min = self.cell(center - Vec3::splat(radius));
+        let max = self.cell(center + Vec3::splat(radius));
+        let (min_z, max_z) = if flat { (0, 0) } else { (min.z, max.z) };
+
+        // When the box covers more cells than are occupied, go through the occupied ones
+        let span = max.as_vec3() - min.as_vec3() + Vec3::ONE;
+        let span = span.x * span.y * (max_z as f32 - min_z as f32 + 1.0);
+        if span > self.cells.len() as f32 {
+            for (cell, boids) in &self.cells {
+                if cell.cmpge(min).all()
+                    && cell.cmple(max).all()
+                    && (min_z..=max_z).contains(&cell.z)
+                {
+                    out.extend_from_slice(boids);
+                }
+            }
+            return;
+        }
+
+        for z in min_z..=max_z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    if let Some(boids) = self.cells.get(&IVec3::new(x, y, z)) {
+                        out.extend_from_slice(boids);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cell(&self, position: Vec3) -> IVec3 {
+        (position / self.cell_size).floor().as_ivec3()
+    }
+}
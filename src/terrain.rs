@@ -0,0 +1,327 @@
+use bevy::{
+    prelude::{Component, Image, Mesh, Resource, Transform, UVec2, Vec2, Vec3, Vec3Swizzles},
+    render::{
+        mesh::{PrimitiveTopology, VertexAttributeValues},
+        render_resource::TextureFormat,
+    },
+};
+
+/// Something that knows the height of the ground, used by [BoidTerrain].
+///
+/// It's implemented for [BoidHeightmap], [BoidMeshHeight], and any closure taking the
+/// `x` and `z` coordinates, and returning the height of the ground there.
+pub trait BoidHeightProvider: Send + Sync + 'static {
+    /// The height of the ground at `x`, `z`, or `None` if there's no ground there.
+    fn height(&self, x: f32, z: f32) -> Option<f32>;
+}
+
+impl<F> BoidHeightProvider for F
+where
+    F: Fn(f32, f32) -> Option<f32> + Send + Sync + 'static,
+{
+    fn height(&self, x: f32, z: f32) -> Option<f32> {
+        self(x, z)
+    }
+}
+
+/// Resource with the terrain that boids with [BoidAltitude] keep their height above.
+///
+/// ## Example
+/// ```rust,ignore
+/// // Rolling hills
+/// app.insert_resource(BoidTerrain::new(|x: f32, z: f32| {
+///     Some((x / 200.0).sin() * (z / 300.0).cos() * 100.0)
+/// }));
+/// ```
+#[derive(Resource)]
+pub struct BoidTerrain {
+    provider: Box<dyn BoidHeightProvider>,
+}
+
+impl BoidTerrain {
+    pub fn new(provider: impl BoidHeightProvider) -> Self {
+        Self {
+            provider: Box::new(provider),
+        }
+    }
+
+    /// The height of the ground at `x`, `z`, or `None` if there's no ground there.
+    pub fn height(&self, x: f32, z: f32) -> Option<f32> {
+        self.provider.height(x, z)
+    }
+}
+
+/// Component that keeps a 3d boid within a band of heights above the [BoidTerrain].
+///
+/// When the boid is lower than `min` above the ground, it steers up, and when it's higher
+/// than `max`, it steers down. Like [BoidBorder](crate::boids::BoidBorder), the push grows
+/// the further outside the band the boid is.
+#[derive(Component, Clone, Copy)]
+pub struct BoidAltitude {
+    /// The lowest height above the ground the boid prefers.
+    pub min: f32,
+    /// The highest height above the ground the boid prefers.
+    pub max: f32,
+    /// How strongly the boid steers back into the band.
+    pub strength: f32,
+}
+
+impl BoidAltitude {
+    pub fn new(min: f32, max: f32, strength: f32) -> Self {
+        Self { min, max, strength }
+    }
+
+    /// Calculates the force that steers a boid at `position` back into the band.
+    pub(crate) fn calc_steering(&self, position: Vec3, terrain: &BoidTerrain) -> Vec3 {
+        let Some(ground) = terrain.height(position.x, position.z) else {
+            return Vec3::ZERO;
+        };
+
+        let altitude = position.y - ground;
+        let band = (self.max - self.min).max(1.0);
+
+        if altitude < self.min {
+            Vec3::Y * self.strength * (self.min - altitude) / band
+        } else if altitude > self.max {
+            Vec3::NEG_Y * self.strength * (altitude - self.max) / band
+        } else {
+            Vec3::ZERO
+        }
+    }
+}
+
+/// A grid of heights, sampled with bilinear interpolation.
+pub struct BoidHeightmap {
+    heights: Vec<f32>,
+    width: u32,
+    depth: u32,
+    origin: Vec2,
+    size: Vec2,
+}
+
+impl BoidHeightmap {
+    /// Creates a heightmap from `width` by `depth` heights, ordered by x first, then z.
+    ///
+    /// `origin` is the `x`, `z` position of the first height, and `size` is the extent the
+    /// heightmap covers in the world.
+    ///
+    /// # Panics
+    /// Panics if there aren't exactly `width * depth` heights.
+    pub fn new(heights: Vec<f32>, width: u32, depth: u32, origin: Vec2, size: Vec2) -> Self {
+        assert_eq!(
+            heights.len(),
+            (width * depth) as usize,
+            "the heightmap needs exactly `width * depth` heights"
+        );
+
+        Self {
+            heights,
+            width,
+            depth,
+            origin,
+            size,
+        }
+    }
+
+    /// Creates a heightmap from the first channel of an image, where black is `0.0` and white
+    /// is `height_scale`. The image's rows go along the z axis.
+    ///
+    /// Returns `None` if the image's format isn't supported. The supported formats are
+    /// `R8Unorm`, `Rgba8Unorm`, `Rgba8UnormSrgb`, `R16Unorm` and `R32Float`.
+    pub fn from_image(image: &Image, origin: Vec2, size: Vec2, height_scale: f32) -> Option<Self> {
+        let data = &image.data;
+
+        let heights = match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => data
+                .iter()
+                .map(|value| *value as f32 / u8::MAX as f32)
+                .collect(),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => data
+                .chunks_exact(4)
+                .map(|pixel| pixel[0] as f32 / u8::MAX as f32)
+                .collect(),
+            TextureFormat::R16Unorm => data
+                .chunks_exact(2)
+                .map(|value| u16::from_le_bytes([value[0], value[1]]) as f32 / u16::MAX as f32)
+                .collect::<Vec<_>>(),
+            TextureFormat::R32Float => data
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect(),
+            _ => return None,
+        }
+        .into_iter()
+        .map(|height: f32| height * height_scale)
+        .collect();
+
+        Some(Self::new(
+            heights,
+            image.width(),
+            image.height(),
+            origin,
+            size,
+        ))
+    }
+
+    fn get(&self, x: u32, z: u32) -> f32 {
+        self.heights[(x + z * self.width) as usize]
+    }
+}
+
+impl BoidHeightProvider for BoidHeightmap {
+    fn height(&self, x: f32, z: f32) -> Option<f32> {
+        if self.width == 0 || self.depth == 0 {
+            return None;
+        }
+
+        let cells = Vec2::new(
+            (self.width - 1).max(1) as f32,
+            (self.depth - 1).max(1) as f32,
+        );
+        let local = (Vec2::new(x, z) - self.origin) / self.size * cells;
+        if local.cmplt(Vec2::ZERO).any() || local.cmpgt(cells).any() {
+            return None;
+        }
+
+        let x0 = (local.x.floor() as u32).min(self.width - 1);
+        let z0 = (local.y.floor() as u32).min(self.depth - 1);
+        let x1 = (x0 + 1).min(self.width - 1);
+        let z1 = (z0 + 1).min(self.depth - 1);
+        let t = local - Vec2::new(x0 as f32, z0 as f32);
+
+        let near = self.get(x0, z0) + (self.get(x1, z0) - self.get(x0, z0)) * t.x;
+        let far = self.get(x0, z1) + (self.get(x1, z1) - self.get(x0, z1)) * t.x;
+
+        Some(near + (far - near) * t.y)
+    }
+}
+
+/// The heights of a triangle mesh, looked up from above.
+///
+/// Where the mesh overlaps itself, the highest surface is used.
+pub struct BoidMeshHeight {
+    triangles: Vec<[Vec3; 3]>,
+    cells: Vec<Vec<u32>>,
+    min: Vec2,
+    cell_size: Vec2,
+    resolution: u32,
+}
+
+impl BoidMeshHeight {
+    /// Creates the heights from a mesh placed with `transform`.
+    ///
+    /// Returns `None` if the mesh isn't a triangle list with 3d positions.
+    pub fn from_mesh(mesh: &Mesh, transform: Transform) -> Option<Self> {
+        let triangles = mesh_triangles(mesh, transform)?;
+
+        let (min, max) = triangles.iter().flatten().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), vertex| (min.min(vertex.xz()), max.max(vertex.xz())),
+        );
+
+        // Aim for a handful of triangles per cell
+        let resolution = ((triangles.len() as f32).sqrt() as u32).clamp(1, 256);
+        let cell_size = ((max - min) / resolution as f32).max(Vec2::splat(f32::EPSILON));
+
+        let mut cells = vec![Vec::new(); (resolution * resolution) as usize];
+        for (index, triangle) in triangles.iter().enumerate() {
+            let (triangle_min, triangle_max) = triangle.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(min, max), vertex| (min.min(vertex.xz()), max.max(vertex.xz())),
+            );
+
+            let start = ((triangle_min - min) / cell_size).as_uvec2();
+            let end = ((triangle_max - min) / cell_size)
+                .as_uvec2()
+                .min(UVec2::splat(resolution - 1));
+
+            for z in start.y..=end.y {
+                for x in start.x..=end.x {
+                    cells[(x + z * resolution) as usize].push(index as u32);
+                }
+            }
+        }
+
+        Some(Self {
+            triangles,
+            cells,
+            min,
+            cell_size,
+            resolution,
+        })
+    }
+}
+
+impl BoidHeightProvider for BoidMeshHeight {
+    fn height(&self, x: f32, z: f32) -> Option<f32> {
+        let point = Vec2::new(x, z);
+        let cell = ((point - self.min) / self.cell_size).floor();
+        if cell.cmplt(Vec2::ZERO).any() || cell.cmpge(Vec2::splat(self.resolution as f32)).any() {
+            return None;
+        }
+        let cell = cell.as_uvec2();
+
+        self.cells[(cell.x + cell.y * self.resolution) as usize]
+            .iter()
+            .filter_map(|index| {
+                let [a, b, c] = self.triangles[*index as usize];
+
+                // Barycentric coordinates of the point in the triangle, seen from above
+                let v0 = b.xz() - a.xz();
+                let v1 = c.xz() - a.xz();
+                let v2 = point - a.xz();
+                let denominator = v0.x * v1.y - v1.x * v0.y;
+                if denominator.abs() <= f32::EPSILON {
+                    return None;
+                }
+
+                let v = (v2.x * v1.y - v1.x * v2.y) / denominator;
+                let w = (v0.x * v2.y - v2.x * v0.y) / denominator;
+                let u = 1.0 - v - w;
+                if u < 0.0 || v < 0.0 || w < 0.0 {
+                    return None;
+                }
+
+                Some(a.y * u + b.y * v + c.y * w)
+            })
+            .reduce(f32::max)
+    }
+}
+
+/// Collects the triangles of a mesh, placed with `transform`.
+///
+/// Returns `None` if the mesh isn't a triangle list with 3d positions.
+pub(crate) fn mesh_triangles(mesh: &Mesh, transform: Transform) -> Option<Vec<[Vec3; 3]>> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+
+    let positions = positions
+        .iter()
+        .map(|position| transform.transform_point(Vec3::from(*position)))
+        .collect::<Vec<_>>();
+
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().collect::<Vec<_>>(),
+        None => (0..positions.len()).collect(),
+    };
+
+    Some(
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                [
+                    positions[triangle[0]],
+                    positions[triangle[1]],
+                    positions[triangle[2]],
+                ]
+            })
+            .collect(),
+    )
+}